@@ -2,14 +2,16 @@
 extern crate criterion;
 use std::iter::repeat_with;
 
-use criterion::{Criterion, SamplingMode};
+use criterion::{black_box, Criterion, SamplingMode};
 use glass_pumpkin::{prime as gprime, safe_prime as safe_gprime};
 use num_bigint::RandBigInt;
-use num_prime::{nt_funcs, RandPrime};
+use num_modular::ModularCoreOps;
+use num_prime::{nt_funcs, Modulus, RandPrime};
 #[cfg(feature = "num-primes")]
 use num_primes::{Generator, Verification};
 use number_theory::NumberTheory;
 use primal_check::miller_rabin;
+use rand::Rng;
 
 pub fn bench_is_prime(c: &mut Criterion) {
     const N0: u64 = 1_000_000;
@@ -204,6 +206,79 @@ pub fn bench_factorization(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn bench_modular_mul(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let m: u64 = rng.gen::<u64>() | (1 << 63);
+    let numbers: Vec<(u64, u64)> = repeat_with(|| (rng.gen::<u64>() % m, rng.gen::<u64>() % m))
+        .take(1024)
+        .collect();
+    let mut group = c.benchmark_group("modular multiplication (u64)");
+
+    group.bench_function("mulm", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .fold(1u64, |acc, &(x, y)| acc ^ x.mulm(y, &m))
+        })
+    });
+    let ring = Modulus::new(m);
+    group.bench_function("Modulus (barrett)", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .fold(1u64, |acc, &(x, y)| acc ^ ring.mul(&x, &y))
+        })
+    });
+
+    group.finish();
+}
+
+pub fn bench_gcd(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let numbers: Vec<(u64, u64)> = repeat_with(|| (rng.gen(), rng.gen())).take(1024).collect();
+    let mut group = c.benchmark_group("gcd (u64)");
+
+    group.bench_function("num-integer", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .fold(0u64, |acc, &(x, y)| acc ^ num_integer::gcd(x, y))
+        })
+    });
+    group.bench_function("binary gcd (this crate)", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .fold(0u64, |acc, &(x, y)| acc ^ nt_funcs::binary_gcd(x, y))
+        })
+    });
+    group.finish();
+
+    let numbers: Vec<_> = repeat_with(|| (rng.gen_biguint(1024), rng.gen_biguint(1024)))
+        .take(64)
+        .collect();
+    let mut group = c.benchmark_group("gcd (1024 bits)");
+    group.bench_function("num-bigint", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .for_each(|(x, y)| {
+                    black_box(num_integer::Integer::gcd(x, y));
+                })
+        })
+    });
+    group.bench_function("binary gcd (this crate)", |b| {
+        b.iter(|| {
+            numbers
+                .iter()
+                .for_each(|(x, y)| {
+                    black_box(nt_funcs::binary_gcd(x.clone(), y.clone()));
+                })
+        })
+    });
+    group.finish();
+}
+
 pub fn bench_prime_gen(c: &mut Criterion) {
     let mut group = c.benchmark_group("prime generation (256 bits)");
     group.sample_size(10).sampling_mode(SamplingMode::Flat);
@@ -236,6 +311,8 @@ criterion_group!(
     benches,
     bench_is_prime,
     bench_factorization,
+    bench_modular_mul,
+    bench_gcd,
     bench_prime_gen
 );
 criterion_main!(benches);
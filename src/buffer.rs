@@ -10,28 +10,39 @@
 //! but it will be efficient enough for most applications.
 //!
 
-use crate::factor::{pollard_rho, trial_division};
+use crate::factor::{pollard_rho_with, trial_division};
 use crate::nt_funcs::{
-    factorize128, is_prime64, next_prime, nth_prime_bounds, nth_prime_est, prev_prime,
+    binary_gcd, factorize128_annotated, factorize64, is_perfect_square, is_prime64_explain,
+    next_prime, nth_prime_bounds, nth_prime_est, prev_prime, prime_pi_bounds, try_from_u64,
 };
 use crate::primality::{PrimalityBase, PrimalityRefBase};
-use crate::tables::{SMALL_PRIMES, SMALL_PRIMES_NEXT};
+use crate::tables::{SMALL_PRIMES, SMALL_PRIMES_NEXT, WHEEL_NEXT, WHEEL_SIZE};
 use crate::traits::{
-    FactorizationConfig, Primality, PrimalityTestConfig, PrimalityUtils, PrimeBuffer,
+    FactorMethod, FactorizationConfig, Primality, PrimalityTestConfig, PrimalityUtils, PrimeBuffer,
 };
-use bitvec::{bitvec, prelude::Msb0};
+use bitvec::{bitvec, prelude::Msb0, vec::BitVec};
 use lru::LruCache;
 use num_integer::Roots;
 use rand::random;
 use std::collections::BTreeMap;
 use std::num::NonZeroUsize;
+use std::time::Instant;
+
+/// Iterate through the integers in (start, end] that are coprime to [WHEEL_SIZE]. These
+/// are the candidates for trial division after the buffered primes are exhausted, the
+/// composites among them never divide the residual since their factors are already removed.
+fn wheel_candidates(start: u64, end: u64) -> impl Iterator<Item = u64> {
+    let next = |c: u64| c.checked_add(WHEEL_NEXT[(c % WHEEL_SIZE as u64) as usize] as u64);
+    std::iter::successors(next(start), move |&c| next(c)).take_while(move |&c| c <= end)
+}
 
 /// Extension functions that can utilize pre-generated primes
 pub trait PrimeBufferExt: for<'a> PrimeBuffer<'a> {
     /// Test if an integer is a prime.
     ///
-    /// For targets smaller than 2^64, the deterministic [is_prime64] will be used, otherwise
-    /// the primality test algorithms can be specified by the `config` argument.
+    /// For targets smaller than 2^64, the deterministic [is_prime64][crate::nt_funcs::is_prime64] will be used, otherwise
+    /// the primality test algorithms can be specified by the `config` argument. If `config` is [None],
+    /// [PrimalityTestConfig::recommended_for()] is used with the bit size of the target.
     ///
     /// The primality test can be either deterministic or probabilistic for large integers depending on the `config`.
     /// The return value is represented by the enum [Primality], which tells whether the primality test is deterministic
@@ -41,33 +52,94 @@ pub trait PrimeBufferExt: for<'a> PrimeBuffer<'a> {
         target: &T,
         config: Option<PrimalityTestConfig>,
     ) -> Primality
+    where
+        for<'r> &'r T: PrimalityRefBase<T>,
+    {
+        self.is_prime_explain(target, config).0
+    }
+
+    /// Test if an integer is a prime, and also return the name of the decision path that
+    /// determined the result. The primality result is the same as [is_prime][Self::is_prime].
+    ///
+    /// The possible decision paths are:
+    /// - `"trivial"`: the target is even or smaller than 2
+    /// - `"small-prime-table"`: the target is looked up in the small prime table
+    /// - `"trial-division-composite"`: the target (< 2^64) has a small prime factor,
+    ///   or a factor under the bound of [PrimalityTestConfig::trial_only]
+    /// - `"trial-division"`: the target is only tested by [PrimalityTestConfig::trial_only]
+    /// - `"miller-rabin-deterministic-u64"`: deterministic Miller-Rabin tests for targets < 2^64
+    /// - `"miller-rabin"`: the target is rejected by, or only tested with the Miller-Rabin tests
+    /// - `"euler"`: the target is rejected by, or only tested with the Euler probable prime test
+    /// - `"lucas"`: the target is rejected by, or only tested with the Lucas probable prime tests
+    /// - `"bpsw"`: the target passes both Miller-Rabin and Lucas probable prime tests
+    fn is_prime_explain<T: PrimalityBase>(
+        &self,
+        target: &T,
+        config: Option<PrimalityTestConfig>,
+    ) -> (Primality, &'static str)
     where
         for<'r> &'r T: PrimalityRefBase<T>,
     {
         // shortcuts
         if target.is_even() {
             return if target == &T::from_u8(2u8).unwrap() {
-                Primality::Yes
+                (Primality::Yes, "trivial")
             } else {
-                Primality::No
+                (Primality::No, "trivial")
             };
         }
+        if target.is_one() {
+            return (Primality::No, "trivial");
+        }
+
+        // trial division with odd divisors only if requested
+        if let Some(bound) = config.and_then(|c| c.trial_bound) {
+            let mut d = 3u64;
+            loop {
+                let td: T = match try_from_u64(d) {
+                    Some(td) => td,
+                    None => return (Primality::Yes, "trial-division"), // d exceeds the target
+                };
+                let (q, r) = target.div_rem(&td);
+                if td > q {
+                    return (Primality::Yes, "trial-division");
+                }
+                if d > bound {
+                    return (Primality::Probable(0.), "trial-division");
+                }
+                if r.is_zero() {
+                    return (Primality::No, "trial-division-composite");
+                }
+                d += 2;
+            }
+        }
 
         // do deterministic test if target is under 2^64
         if let Some(x) = target.to_u64() {
-            return match is_prime64(x) {
-                true => Primality::Yes,
-                false => Primality::No,
+            let (result, path) = is_prime64_explain(x);
+            return match result {
+                true => (Primality::Yes, path),
+                false => (Primality::No, path),
             };
         }
 
-        let config = config.unwrap_or(PrimalityTestConfig::default());
+        let config = config.unwrap_or_else(|| PrimalityTestConfig::recommended_for(target.bits()));
         let mut probability = 1.;
 
         // miller-rabin test
         let mut witness_list: Vec<u64> = Vec::new();
+        if config.fast_reject {
+            // the small bases are tested first, they don't count in the probability
+            witness_list.extend([2, 3, 5]);
+        }
         if config.sprp_trials > 0 {
-            witness_list.extend(self.iter().take(config.sprp_trials));
+            let bases: Vec<u64> = self
+                .iter()
+                .take(config.sprp_trials)
+                .filter(|p| !witness_list.contains(p))
+                .cloned()
+                .collect();
+            witness_list.extend(bases);
             probability *= 1. - 0.25f32.powi(config.sprp_trials as i32);
         }
         if config.sprp_random_trials > 0 {
@@ -81,28 +153,105 @@ pub trait PrimeBufferExt: for<'a> PrimeBuffer<'a> {
             }
             probability *= 1. - 0.25f32.powi(config.sprp_random_trials as i32);
         }
+        let miller = !witness_list.is_empty();
         if !witness_list
             .into_iter()
             .all(|x| target.is_sprp(T::from_u64(x).unwrap()))
         {
-            return Primality::No;
+            return (Primality::No, "miller-rabin");
+        }
+
+        // euler probable prime test
+        if config.euler_test {
+            probability *= 0.5;
+            if !target.is_euler_prp(T::from_u8(2).unwrap()) {
+                return (Primality::No, "euler");
+            }
         }
 
         // lucas probable prime test
+        let lucas = config.slprp_test || config.eslprp_test;
         if config.slprp_test {
             probability *= 1. - 4f32 / 15f32;
             if !target.is_slprp(None, None) {
-                return Primality::No;
+                return (Primality::No, "lucas");
             }
         }
         if config.eslprp_test {
             probability *= 1. - 4f32 / 15f32;
             if !target.is_eslprp(None) {
-                return Primality::No;
+                return (Primality::No, "lucas");
+            }
+        }
+
+        let path = match (miller, lucas) {
+            (true, true) => "bpsw",
+            (false, true) => "lucas",
+            (false, false) if config.euler_test => "euler",
+            _ => "miller-rabin",
+        };
+        (Primality::Probable(probability), path)
+    }
+
+    /// Test primality of a batch of integers.
+    ///
+    /// A single trial division pass with the small primes is done across all targets before
+    /// running the per-number tests, which is more efficient than calling [is_prime][Self::is_prime]
+    /// repeatedly when most of the candidates have small factors. The results are the same as
+    /// the individual [is_prime][Self::is_prime] calls.
+    fn is_prime_batch<T: PrimalityBase>(
+        &self,
+        targets: &[T],
+        config: Option<PrimalityTestConfig>,
+    ) -> Vec<Primality>
+    where
+        for<'r> &'r T: PrimalityRefBase<T>,
+    {
+        if config.is_some_and(|c| c.trial_bound.is_some()) {
+            return targets.iter().map(|t| self.is_prime(t, config)).collect();
+        }
+
+        let two = T::from_u8(2).unwrap();
+        let mut results: Vec<Option<Primality>> = targets
+            .iter()
+            .map(|t| (t < &two).then_some(Primality::No))
+            .collect();
+
+        // shared trial division pass, which stops when p doesn't fit in T or p^2 exceeds
+        // all the targets, the remaining targets are primes in both cases
+        let max_target = match targets.iter().max() {
+            Some(t) => t,
+            None => return Vec::new(),
+        };
+        for &p in SMALL_PRIMES.iter() {
+            let tp: T = match T::from_u64(p as u64) {
+                Some(tp) => tp,
+                None => break,
+            };
+            if max_target / &tp < tp {
+                break;
+            }
+            for (t, r) in targets.iter().zip(results.iter_mut()) {
+                if r.is_none() && (t % &tp).is_zero() {
+                    *r = Some(match t == &tp {
+                        true => Primality::Yes,
+                        false => Primality::No,
+                    });
+                }
             }
         }
 
-        Primality::Probable(probability)
+        // per-number tests on the remaining candidates
+        let psq = SMALL_PRIMES_NEXT * SMALL_PRIMES_NEXT;
+        targets
+            .iter()
+            .zip(results)
+            .map(|(t, r)| match r {
+                Some(r) => r,
+                None if t.to_u64().is_some_and(|x| x < psq) => Primality::Yes,
+                None => self.is_prime(t, config),
+            })
+            .collect()
     }
 
     /// Factorize an integer.
@@ -122,59 +271,101 @@ pub trait PrimeBufferExt: for<'a> PrimeBuffer<'a> {
     where
         for<'r> &'r T: PrimalityRefBase<T>,
     {
-        // shortcut if the target is in u128 range
-        if let Some(x) = target.to_u128() {
-            let factors = factorize128(x)
+        let (result, failed) = factors_with_methods(self, target, config);
+        let result = result.into_iter().map(|(p, (exp, _))| (p, exp)).collect();
+        if failed.is_empty() {
+            (result, None)
+        } else {
+            (result, Some(failed))
+        }
+    }
+
+    /// Factorize an integer like [factors][Self::factors()], and also report the method that
+    /// found each prime factor, see [FactorMethod] for the possible values. This helps
+    /// understanding and tuning the factorization performance on a specific workload.
+    ///
+    /// If a prime factor is found multiple times, the method of the first discovery is reported.
+    /// If the factorization failed, the composite cofactors that are not factored are returned
+    /// as the error. Zero and one are not factored, and the result is an empty map.
+    fn factors_annotated<T: PrimalityBase>(
+        &self,
+        target: T,
+        config: Option<FactorizationConfig>,
+    ) -> Result<BTreeMap<T, (usize, FactorMethod)>, Vec<T>>
+    where
+        for<'r> &'r T: PrimalityRefBase<T>,
+    {
+        let (result, failed) = factors_with_methods(self, target, config);
+        if failed.is_empty() {
+            Ok(result)
+        } else {
+            Err(failed)
+        }
+    }
+
+    /// Factorize an integer on a best-effort basis before the given deadline.
+    ///
+    /// It works like [factors][Self::factors()], but the factorization is stopped once the
+    /// deadline is reached. The clock is checked between the primality tests and every 1024
+    /// iterations of Pollard's rho, so the function returns shortly after the deadline. The prime
+    /// factors found so far are returned together with the product of the remaining unfactored
+    /// parts (None if fully factored). Only the primality test config and the trial division
+    /// limit are used from `config`, the number of rho trials is bounded by the deadline.
+    fn factors_with_timeout<T: PrimalityBase>(
+        &self,
+        target: T,
+        config: Option<FactorizationConfig>,
+        deadline: Instant,
+    ) -> (BTreeMap<T, usize>, Option<T>)
+    where
+        for<'r> &'r T: PrimalityRefBase<T>,
+    {
+        if Instant::now() >= deadline {
+            return (BTreeMap::new(), Some(target));
+        }
+        if let Some(x) = target.to_u64() {
+            let factors = factorize64(x)
                 .into_iter()
-                .map(|(k, v)| (T::from_u128(k).unwrap(), v))
+                .map(|(k, v)| (T::from_u64(k).unwrap(), v))
                 .collect();
             return (factors, None);
         }
-        let config = config.unwrap_or(FactorizationConfig::default());
+        let config = config.unwrap_or_default();
 
-        // test the existing primes
-        let (result, factored) = trial_division(self.iter().cloned(), target, config.td_limit);
+        let wheel = wheel_candidates(self.bound(), config.td_limit.unwrap_or(0));
+        let primes = self.iter().cloned().chain(wheel);
+        let (result, factored) = trial_division(primes, target, config.td_limit);
         let mut result: BTreeMap<T, usize> = result
             .into_iter()
             .map(|(k, v)| (T::from_u64(k).unwrap(), v))
             .collect();
 
-        // TODO: check is_perfect_power before other methods
-
-        // find factors by dividing
-        let mut failed = Vec::new();
-        let mut config = config;
-        config.td_limit = Some(0); // disable trial division when finding divisor
-        match factored {
-            Ok(res) => {
-                if !res.is_one() {
-                    result.insert(res, 1);
-                }
+        // the residual is either a prime or needs to be split further
+        let mut todo = vec![factored.unwrap_or_else(|res| res)];
+        let mut cofactor = T::one();
+        while let Some(target) = todo.pop() {
+            if target.is_one() {
+                continue;
             }
-            Err(res) => {
-                let mut todo = vec![res];
-                while let Some(target) = todo.pop() {
-                    if self
-                        .is_prime(&target, Some(config.primality_config))
-                        .probably()
-                    {
-                        *result.entry(target).or_insert(0) += 1;
-                    } else {
-                        if let Some(divisor) = self.divisor(&target, &mut config) {
-                            todo.push(divisor.clone());
-                            todo.push(target / divisor);
-                        } else {
-                            failed.push(target);
-                        }
-                    }
-                }
+            if Instant::now() >= deadline {
+                cofactor = cofactor * target;
+            } else if self
+                .is_prime(&target, Some(config.primality_config))
+                .probably()
+            {
+                *result.entry(target).or_insert(0) += 1;
+            } else if let Some(divisor) = divisor_before(&target, deadline) {
+                todo.push(divisor.clone());
+                todo.push(target / divisor);
+            } else {
+                cofactor = cofactor * target;
             }
-        };
+        }
 
-        if failed.is_empty() {
+        if cofactor.is_one() {
             (result, None)
         } else {
-            (result, Some(failed))
+            (result, Some(cofactor))
         }
     }
 
@@ -203,60 +394,263 @@ pub trait PrimeBufferExt: for<'a> PrimeBuffer<'a> {
     where
         for<'r> &'r T: PrimalityRefBase<T>,
     {
-        if matches!(config.td_limit, Some(0)) {
-            // try to get a factor by trial division
-            let tsqrt: T = Roots::sqrt(target) + T::one();
-            let limit = if let Some(l) = config.td_limit {
-                tsqrt.clone().min(T::from_u64(l).unwrap())
-            } else {
-                tsqrt.clone()
-            };
+        divisor_with_method(self, target, config).map(|(d, _)| d)
+    }
+}
 
-            for p in self.iter().map(|p| T::from_u64(*p).unwrap()) {
-                if &p > &tsqrt {
-                    return None; // the number is a prime
-                }
-                if &p > &limit {
-                    break;
-                }
-                if target.is_multiple_of(&p) {
-                    return Some(p);
+impl<T> PrimeBufferExt for T where for<'a> T: PrimeBuffer<'a> {}
+
+// Factorize an integer with the method that found each prime factor, the composite cofactors
+// that are not factored are returned as the second part. This is shared by
+// PrimeBufferExt::factors() and PrimeBufferExt::factors_annotated().
+fn factors_with_methods<B: PrimeBufferExt + ?Sized, T: PrimalityBase>(
+    pb: &B,
+    target: T,
+    config: Option<FactorizationConfig>,
+) -> (BTreeMap<T, (usize, FactorMethod)>, Vec<T>)
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if target.is_zero() || target.is_one() {
+        return (BTreeMap::new(), Vec::new());
+    }
+
+    // shortcut if the target is in u128 range
+    if let Some(x) = target.to_u128() {
+        let factors = factorize128_annotated(x)
+            .into_iter()
+            .map(|(k, v)| (T::from_u128(k).unwrap(), v))
+            .collect();
+        return (factors, Vec::new());
+    }
+    let config = config.unwrap_or_default();
+
+    // test the existing primes, then continue with the wheel candidates up to the trial
+    // division limit, so that the buffer doesn't need to be extended
+    let wheel = wheel_candidates(pb.bound(), config.td_limit.unwrap_or(0));
+    let primes = pb.iter().cloned().chain(wheel);
+    let (result, factored) = trial_division(primes, target, config.td_limit);
+    let mut result: BTreeMap<T, (usize, FactorMethod)> = result
+        .into_iter()
+        .map(|(k, v)| (T::from_u64(k).unwrap(), (v, FactorMethod::TrialDivision)))
+        .collect();
+    // the residual is the target itself if no small factor is found
+    let method = match result.is_empty() {
+        true => FactorMethod::Prime,
+        false => FactorMethod::TrialDivision,
+    };
+
+    // find factors by dividing
+    let mut failed = Vec::new();
+    let mut config = config;
+    config.td_limit = Some(0); // disable trial division when finding divisor
+    match factored {
+        Ok(res) => {
+            if !res.is_one() {
+                result.insert(res, (1, method));
+            }
+        }
+        Err(res) => {
+            let mut todo = vec![(res, method)];
+            while let Some((target, method)) = todo.pop() {
+                if pb
+                    .is_prime(&target, Some(config.primality_config))
+                    .probably()
+                {
+                    result.entry(target).or_insert((0, method)).0 += 1;
+                } else if let Some((divisor, method)) =
+                    divisor_with_method(pb, &target, &mut config)
+                {
+                    todo.push((divisor.clone(), method));
+                    todo.push((target / divisor, method));
+                } else {
+                    failed.push(target);
                 }
             }
         }
+    };
+    (result, failed)
+}
 
-        // try to get a factor using pollard_rho with 4x4 trials
-        let below64 = target.to_u64().is_some();
-        while config.rho_trials > 0 {
-            let (start, offset) = if below64 {
-                (
-                    T::from_u8(random::<u8>()).unwrap() % target,
-                    T::from_u8(random::<u8>()).unwrap() % target,
-                )
+// Find a proper divisor of the target like PrimeBufferExt::divisor(), and also report the
+// method that found it
+fn divisor_with_method<B: PrimeBufferExt + ?Sized, T: PrimalityBase>(
+    pb: &B,
+    target: &T,
+    config: &mut FactorizationConfig,
+) -> Option<(T, FactorMethod)>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if matches!(config.td_limit, Some(0)) {
+        // try to get a factor by trial division
+        let tsqrt: T = Roots::sqrt(target) + T::one();
+        let limit = if let Some(l) = config.td_limit {
+            tsqrt.clone().min(T::from_u64(l).unwrap())
+        } else {
+            tsqrt.clone()
+        };
+
+        for p in pb.iter().map(|p| T::from_u64(*p).unwrap()) {
+            if &p > &tsqrt {
+                return None; // the number is a prime
+            }
+            if &p > &limit {
+                break;
+            }
+            if target.is_multiple_of(&p) {
+                return Some((p, FactorMethod::TrialDivision));
+            }
+        }
+    }
+
+    // pollard rho can't split a perfect square, extract the square root directly
+    if is_perfect_square(target) {
+        return Some((Roots::sqrt(target), FactorMethod::PerfectPower));
+    }
+
+    // try to get a factor using pollard_rho with 4x4 trials
+    let gcd: fn(&T, &T) -> T = match config.binary_gcd {
+        true => |a, b| binary_gcd(a.clone(), b.clone()),
+        false => |a, b| a.gcd(b),
+    };
+    let below64 = target.to_u64().is_some();
+    while config.rho_trials > 0 {
+        let (start, offset) = if below64 {
+            (
+                T::from_u8(random::<u8>()).unwrap() % target,
+                T::from_u8(random::<u8>()).unwrap() % target,
+            )
+        } else {
+            (
+                T::from_u64(random::<u64>()).unwrap() % target,
+                T::from_u64(random::<u64>()).unwrap() % target,
+            )
+        };
+        config.rho_trials -= 1;
+        // TODO: change to a reasonable pollard rho limit
+        // TODO: add other factorization methods
+        if let (Some(p), _) = pollard_rho_with(target, start, offset, 1048576, gcd, || false) {
+            return Some((p, FactorMethod::PollardRho));
+        }
+    }
+
+    None
+}
+
+/// Generate a random integer below the target, in a way that doesn't overflow small types
+pub(crate) fn random_below<T: PrimalityBase>(target: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    match target.to_u64() {
+        Some(t) => T::from_u64(random::<u64>() % t).unwrap(),
+        None => T::from_u64(random::<u64>()).unwrap() % target,
+    }
+}
+
+/// Find a proper divisor of a composite with Pollard's rho method, restarting with random
+/// parameters and a doubled iteration budget each round, until the deadline is reached. The
+/// clock is also checked within each round.
+fn divisor_before<T: PrimalityBase>(target: &T, deadline: Instant) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    // pollard rho can't split a perfect square, extract the square root directly
+    if is_perfect_square(target) {
+        return Some(Roots::sqrt(target));
+    }
+
+    let mut max_iter = 1 << 12;
+    while Instant::now() < deadline {
+        let start = random_below(target);
+        let offset = random_below(target);
+        let expired = || Instant::now() >= deadline;
+        let gcd = |a: &T, b: &T| a.gcd(b);
+        if let (Some(p), _) = pollard_rho_with(target, start, offset, max_iter, gcd, expired) {
+            return Some(p);
+        }
+        max_iter = (max_iter * 2).min(1 << 20);
+    }
+    None
+}
+
+/// Iterator of the primes up to a limit, which are generated by a segmented sieve with bounded
+/// memory. See [NaiveBuffer::primes_bounded_memory()].
+pub struct SegmentedPrimes<'a> {
+    base: &'a [u64], // odd primes up to the square root of the limit
+    limit: u64,
+    span: u64, // the number of odd integers in each segment
+    low: u64,  // the odd integer represented by the first bit in the sieve
+    sieve: BitVec<usize, Msb0>,
+    pos: usize,
+    two: bool,
+}
+
+impl SegmentedPrimes<'_> {
+    /// Sieve the odd integers starting from `low`
+    fn sieve_from(&mut self, low: u64) {
+        let count = self.span.min((self.limit - low) / 2 + 1);
+        let high = low + 2 * (count - 1); // inclusive
+        self.sieve.clear();
+        self.sieve.resize(count as usize, false);
+        for &p in self.base {
+            if p * p > high {
+                break;
+            }
+            let start = if p * p < low {
+                p * (((low - 1) / p + 1) | 1) // start from an odd factor
             } else {
-                (
-                    T::from_u64(random::<u64>()).unwrap() % target,
-                    T::from_u64(random::<u64>()).unwrap() % target,
-                )
+                p * p
             };
-            config.rho_trials -= 1;
-            // TODO: change to a reasonable pollard rho limit
-            // TODO: add other factorization methods
-            if let (Some(p), _) = pollard_rho(target, start, offset, 1048576) {
-                return Some(p);
+            for multi in (start..=high).step_by(2 * p as usize) {
+                self.sieve.set(((multi - low) / 2) as usize, true);
             }
         }
-
-        None
+        if low == 1 {
+            self.sieve.set(0, true);
+        }
+        self.low = low;
+        self.pos = 0;
     }
 }
 
-impl<T> PrimeBufferExt for T where for<'a> T: PrimeBuffer<'a> {}
+impl Iterator for SegmentedPrimes<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.two {
+            self.two = false;
+            return Some(2);
+        }
+        loop {
+            while self.pos < self.sieve.len() {
+                let i = self.pos;
+                self.pos += 1;
+                if !self.sieve[i] {
+                    return Some(self.low + 2 * i as u64);
+                }
+            }
+
+            // move to the next segment
+            let low = match self.sieve.len() {
+                0 => 1,
+                len => self.low.checked_add(2 * len as u64)?,
+            };
+            if low > self.limit {
+                return None;
+            }
+            self.sieve_from(low);
+        }
+    }
+}
 
 /// NaiveBuffer implements a very simple Sieve of Eratosthenes
 pub struct NaiveBuffer {
     list: Vec<u64>, // list of found prime numbers
     next: u64, // all primes smaller than this value has to be in the prime list, should be an odd number
+    // cached factorization results, see factors_cached()
+    factor_cache: BTreeMap<u64, BTreeMap<u64, usize>>,
 }
 
 impl NaiveBuffer {
@@ -266,6 +660,7 @@ impl NaiveBuffer {
         NaiveBuffer {
             list,
             next: SMALL_PRIMES_NEXT,
+            factor_cache: BTreeMap::new(),
         }
     }
 }
@@ -281,6 +676,7 @@ impl<'a> PrimeBuffer<'a> for NaiveBuffer {
         self.list.truncate(16);
         self.list.shrink_to_fit();
         self.next = 55; // 16-th prime is 53
+        self.factor_cache.clear();
     }
 
     fn iter(&'a self) -> Self::PrimeIter {
@@ -302,7 +698,10 @@ impl<'a> PrimeBuffer<'a> for NaiveBuffer {
         // create sieve and filter with existing primes
         let mut sieve = bitvec![usize, Msb0; 0; ((sieve_limit - current) / 2) as usize];
         for p in self.list.iter().skip(1) {
-            // skip pre-filtered 2
+            // skip pre-filtered 2, and stop when the multiples are beyond the sieve
+            if p * p >= sieve_limit {
+                break;
+            }
             let start = if p * p < current {
                 p * ((current / p) | 1) // start from an odd factor
             } else {
@@ -354,6 +753,43 @@ impl NaiveBuffer {
         return self.list.iter().take(position);
     }
 
+    /// Returns all primes ≤ `limit` like [Self::primes], but the memory used is kept under
+    /// `max_bytes`. The primes are generated segment by segment with a sieve, and only the
+    /// primes up to `sqrt(limit)` are stored in the buffer (each takes 8 bytes), so that a large
+    /// limit doesn't cause an excessive allocation.
+    ///
+    /// If the memory limit is not enough for the stored primes and at least one byte of sieve,
+    /// the required number of bytes is returned as the error.
+    pub fn primes_bounded_memory(
+        &mut self,
+        limit: u64,
+        max_bytes: usize,
+    ) -> Result<SegmentedPrimes<'_>, usize> {
+        // check the memory with the upper bound of π(sqrt(limit)) before sieving
+        let root = Roots::sqrt(&limit);
+        let (_, count) = prime_pi_bounds(&root);
+        let required = (count as usize).max(1) * 8 + 1;
+        if max_bytes < required {
+            return Err(required);
+        }
+
+        self.reserve(root);
+        let position = match self.list.binary_search(&root) {
+            Ok(p) => p + 1,
+            Err(p) => p,
+        };
+        let span = ((max_bytes - required + 1) as u64).saturating_mul(8);
+        Ok(SegmentedPrimes {
+            base: &self.list[1..position.max(1)], // skip 2
+            limit,
+            span,
+            low: 1,
+            sieve: BitVec::new(),
+            pos: 0,
+            two: limit >= 2,
+        })
+    }
+
     /// Returns all primes ≤ `limit` and takes ownership. The primes are sorted.
     pub fn into_primes(mut self, limit: u64) -> std::vec::IntoIter<u64> {
         self.reserve(limit);
@@ -414,6 +850,17 @@ impl NaiveBuffer {
         x
     }
 
+    /// Factorize an integer and store the result in the buffer, so that repeated factorization
+    /// on the same integer will be returned directly from the cache.
+    ///
+    /// The cache grows with each new input, it can be released by [PrimeBuffer::clear()].
+    pub fn factors_cached(&mut self, target: u64) -> BTreeMap<u64, usize> {
+        self.factor_cache
+            .entry(target)
+            .or_insert_with(|| factorize64(target))
+            .clone()
+    }
+
     /// Legendre's phi function, used as a helper function for [Self::prime_pi]
     pub fn prime_phi(&mut self, x: u64, a: usize, cache: &mut LruCache<(u64, usize), u64>) -> u64 {
         if a == 1 {
@@ -479,12 +926,70 @@ impl NaiveBuffer {
 mod tests {
     use super::*;
     use crate::mint::SmallMint;
+    use crate::nt_funcs::is_prime64;
     #[cfg(feature = "num-bigint")]
     use core::str::FromStr;
     #[cfg(feature = "num-bigint")]
     use num_bigint::BigUint;
     use rand::random;
 
+    #[test]
+    fn wheel_candidates_test() {
+        let candidates: Vec<u64> = wheel_candidates(251, 300).collect();
+        let wheel = WHEEL_SIZE as u64;
+        assert!(candidates.iter().all(|&c| num_integer::gcd(c, wheel) == 1));
+        let primes: Vec<u64> = candidates.into_iter().filter(|&c| is_prime64(c)).collect();
+        assert_eq!(primes, [257, 263, 269, 271, 277, 281, 283, 293]);
+        assert_eq!(wheel_candidates(u64::MAX - 2, u64::MAX).count(), 0);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            // factors beyond the buffered primes are found by trial division
+            let pb = NaiveBuffer::new();
+            let mut config = FactorizationConfig::default();
+            config.rho_trials = 0;
+            let p = BigUint::from(u128::MAX >> 1); // 2^127 - 1
+            let target = &p * 10007u32 * 10007u32 * 16381u32;
+            let (fac, failed) = pb.factors(target, Some(config));
+            assert!(failed.is_none());
+            let (p1, p2) = (BigUint::from(10007u16), BigUint::from(16381u16));
+            assert_eq!(fac, BTreeMap::from([(p1, 2), (p2, 1), (p, 1)]));
+        }
+    }
+
+    #[test]
+    fn primes_bounded_memory_test() {
+        let expected: Vec<u64> = NaiveBuffer::new().into_primes(100000).collect();
+        for max_bytes in [800, 2000, 1 << 20] {
+            let mut pb = NaiveBuffer::new();
+            let primes = pb.primes_bounded_memory(100000, max_bytes).unwrap();
+            assert!(primes.eq(expected.iter().cloned()));
+            let primes = pb.primes_bounded_memory(50000, max_bytes).unwrap();
+            assert!(primes.eq(expected.iter().take(5133).cloned()));
+            let primes = pb.primes_bounded_memory(200000, max_bytes).unwrap();
+            assert_eq!(primes.count(), 17984);
+        }
+        for limit in 0..100 {
+            let mut pb = NaiveBuffer::new();
+            let primes = pb.primes_bounded_memory(limit, 64).unwrap();
+            let expected = NaiveBuffer::new().into_primes(limit);
+            assert!(primes.eq(expected), "{}", limit);
+        }
+
+        // the memory is not enough for the primes below sqrt(limit)
+        let mut pb = NaiveBuffer::new();
+        let required = pb.primes_bounded_memory(100000, 0).err();
+        assert!(required.is_some_and(|b| b > 65 * 8));
+        assert!(pb.primes_bounded_memory(100000, 64).is_err());
+        assert!(pb.primes_bounded_memory(u64::MAX, 1 << 20).is_err());
+
+        // the buffer is not extended beyond the square root of the limit
+        let mut pb = NaiveBuffer::new();
+        let primes = pb.primes_bounded_memory(10u64.pow(11), 1 << 20).unwrap();
+        assert_eq!(primes.take(5).collect::<Vec<_>>(), [2, 3, 5, 7, 11]);
+        assert!(pb.bound() < 400000);
+    }
+
     #[test]
     fn prime_generation_test() {
         const PRIME50: [u64; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
@@ -659,6 +1164,312 @@ mod tests {
         }
     }
 
+    #[test]
+    fn is_prime_explain_test() {
+        let pb = NaiveBuffer::new();
+        let path = |t: u128, config| pb.is_prime_explain(&t, config).1;
+
+        assert_eq!(path(0, None), "trivial");
+        assert_eq!(path(2, None), "trivial");
+        assert_eq!(path(1 << 100, None), "trivial");
+        assert_eq!(path(1, None), "trivial");
+        assert_eq!(path(97, None), "small-prime-table");
+        assert_eq!(path(99, None), "small-prime-table");
+        assert_eq!(path(65537 * 3, None), "trial-division-composite");
+        assert_eq!(path(65537, None), "miller-rabin-deterministic-u64");
+        assert_eq!(path(65537 * 65539, None), "miller-rabin-deterministic-u64");
+
+        const P: u128 = 18699199384836356663;
+        assert_eq!(path(P, None), "bpsw");
+        assert_eq!(path(P * 3, None), "miller-rabin");
+        let mr = Some(PrimalityTestConfig::default());
+        assert_eq!(path(P, mr), "miller-rabin");
+        let bpsw = Some(PrimalityTestConfig::bpsw());
+        assert_eq!(path(P, bpsw), "bpsw");
+        assert_eq!(path(P * 3, bpsw), "miller-rabin");
+        let mut config = PrimalityTestConfig::default();
+        config.sprp_trials = 0;
+        config.sprp_random_trials = 0;
+        config.eslprp_test = true;
+        assert_eq!(path(P, Some(config)), "lucas");
+        assert_eq!(path(P * 3, Some(config)), "lucas");
+        config.eslprp_test = false;
+        config.euler_test = true;
+        assert_eq!(path(P, Some(config)), "euler");
+        assert_eq!(path(P * 3, Some(config)), "euler");
+
+        // results agree with is_prime64
+        for _ in 0..1000 {
+            let x = random::<u64>();
+            let (result, _) = pb.is_prime_explain(&x, None);
+            assert_eq!(result.probably(), is_prime64(x));
+        }
+        for x in 0..2000u64 {
+            let (result, _) = pb.is_prime_explain(&x, None);
+            assert_eq!(result.probably(), is_prime64(x));
+        }
+    }
+
+    #[test]
+    fn recommended_config_test() {
+        let config = PrimalityTestConfig::recommended_for(64);
+        assert_eq!(config.sprp_trials, 12);
+        assert!(!config.slprp_test);
+
+        for bits in [65, 100, 128] {
+            let config = PrimalityTestConfig::recommended_for(bits);
+            assert_eq!(config.sprp_trials, 1);
+            assert_eq!(config.sprp_random_trials, 0);
+            assert!(config.slprp_test);
+        }
+        let rounds: Vec<_> = [256, 512, 1024, 2048, 4096]
+            .iter()
+            .map(|&bits| PrimalityTestConfig::recommended_for(bits).sprp_random_trials)
+            .collect();
+        assert_eq!(rounds, [5, 5, 4, 3, 3]);
+        assert!(PrimalityTestConfig::recommended_for(2048).slprp_test);
+
+        // the first 12 prime bases are deterministic for u64
+        let pb = NaiveBuffer::new();
+        let config = Some(PrimalityTestConfig::recommended_for(64));
+        let strict = Some(PrimalityTestConfig::strict());
+        for _ in 0..100 {
+            let x = random::<u64>() as u128 | 1 << 64;
+            assert_eq!(
+                pb.is_prime(&x, config).probably(),
+                pb.is_prime(&x, strict).probably()
+            );
+        }
+    }
+
+    #[test]
+    fn fast_reject_test() {
+        let pb = NaiveBuffer::new();
+        let config = PrimalityTestConfig::default().fast_reject(true);
+        assert!(config.fast_reject);
+
+        // the verdict is the same as the test without fast rejection
+        for _ in 0..200 {
+            let x = random::<u128>() | 1;
+            let expected = pb.is_prime(&x, None).probably();
+            assert_eq!(pb.is_prime(&x, Some(config)).probably(), expected);
+        }
+        const P: u128 = 18699199384836356663;
+        assert!(pb.is_prime(&P, Some(config)).probably());
+        assert!(!pb.is_prime(&(P * 5), Some(config)).probably());
+
+        // only the fast rejection bases
+        let mut config = config;
+        config.sprp_trials = 0;
+        config.sprp_random_trials = 0;
+        assert!(pb.is_prime(&P, Some(config)).probably());
+        assert!(!pb.is_prime(&(P * 7), Some(config)).probably());
+    }
+
+    #[test]
+    fn is_prime_trial_only_test() {
+        let pb = NaiveBuffer::new();
+        let config = Some(PrimalityTestConfig::trial_only(100));
+        for n in 0..5000u64 {
+            let expected = match is_prime64(n) {
+                true => Primality::Yes,
+                false => Primality::No,
+            };
+            assert_eq!(pb.is_prime(&n, config), expected, "n = {}", n);
+        }
+
+        // inconclusive beyond the square of the bound
+        assert_eq!(pb.is_prime(&10007u64, config), Primality::Yes);
+        assert_eq!(pb.is_prime(&65537u64, config), Primality::Probable(0.));
+        let pq = 101u64 * 103;
+        assert_eq!(pb.is_prime(&pq, config), Primality::Probable(0.));
+        assert_eq!(pb.is_prime(&(97u64 * 10007), config), Primality::No);
+        assert_eq!(
+            pb.is_prime_explain(&(97u64 * 10007), config).1,
+            "trial-division-composite"
+        );
+
+        let config = Some(PrimalityTestConfig::trial_only(u64::MAX));
+        assert_eq!(pb.is_prime(&1000003u32, config), Primality::Yes);
+        for n in 0..=u8::MAX {
+            let expected = pb.is_prime(&(n as u64), None);
+            assert_eq!(pb.is_prime(&n, config), expected, "n = {}", n);
+        }
+        assert_eq!(pb.is_prime_batch(&[9u16, 11, 13], config).len(), 3);
+    }
+
+    #[test]
+    fn is_prime_batch_test() {
+        let pb = NaiveBuffer::new();
+
+        let targets: Vec<u64> = (0..2000).chain((0..200).map(|_| random())).collect();
+        let expected: Vec<_> = targets.iter().map(|t| pb.is_prime(t, None)).collect();
+        assert_eq!(pb.is_prime_batch(&targets, None), expected);
+
+        let targets: Vec<u128> = (0..200u128).map(|i| (1 << 89) - 1 + 2 * i).collect();
+        let results = pb.is_prime_batch(&targets, None);
+        for (t, r) in targets.iter().zip(results) {
+            assert_eq!(r.probably(), pb.is_prime(t, None).probably());
+        }
+
+        // small integer types
+        let targets: Vec<u8> = (0..=u8::MAX).collect();
+        let expected: Vec<_> = targets.iter().map(|t| pb.is_prime(t, None)).collect();
+        assert_eq!(pb.is_prime_batch(&targets, None), expected);
+        let targets: Vec<u16> = (0..=u16::MAX).step_by(7).collect();
+        let expected: Vec<_> = targets.iter().map(|t| pb.is_prime(t, None)).collect();
+        assert_eq!(pb.is_prime_batch(&targets, None), expected);
+        assert!(pb.is_prime_batch::<u64>(&[], None).is_empty());
+    }
+
+    #[test]
+    fn factors_cached_test() {
+        let mut pb = NaiveBuffer::new();
+        for _ in 0..10 {
+            let x = random::<u64>();
+            assert_eq!(pb.factors_cached(x), factorize64(x));
+            assert_eq!(pb.factors_cached(x), factorize64(x));
+        }
+        assert_eq!(pb.factor_cache.len(), 10);
+
+        // the stored results should be returned
+        let fake = BTreeMap::from([(2, 1), (3, 1)]);
+        pb.factor_cache.insert(5, fake.clone());
+        assert_eq!(pb.factors_cached(5), fake);
+
+        pb.clear();
+        assert!(pb.factor_cache.is_empty());
+        assert_eq!(pb.factors_cached(5), BTreeMap::from([(5, 1)]));
+    }
+
+    #[test]
+    fn factors_annotated_test() {
+        use FactorMethod::*;
+        let pb = NaiveBuffer::new();
+        let annotated = |t: u128| pb.factors_annotated(t, None).unwrap();
+
+        assert!(annotated(1).is_empty());
+        assert_eq!(annotated(97), BTreeMap::from([(97, (1, Prime))]));
+        let expected = BTreeMap::from([(2, (3, TrialDivision)), (3, (1, TrialDivision))]);
+        assert_eq!(annotated(24), expected);
+        let (p, q) = (1000000007u128, 998244353u128);
+        assert_eq!(annotated(p * 6)[&p], (1, TrialDivision));
+        assert_eq!(annotated(p * p * p)[&p], (3, PerfectPower));
+
+        // hard factors
+        let pq = annotated(p * q);
+        assert_eq!(pq.keys().collect::<Vec<_>>(), [&q, &p]);
+        assert!(pq
+            .values()
+            .all(|&(e, m)| e == 1 && matches!(m, PollardRho | OneLine | Squfof)));
+        let r = 18446744073709551557u128;
+        let pqr = annotated(p * q * r);
+        assert_eq!(pqr.len(), 3);
+        assert!(pqr.values().all(|&(_, m)| m != TrialDivision && m != Prime));
+
+        // the exponents agree with factors()
+        for _ in 0..20 {
+            let x = random::<u64>() as u128;
+            let expected = pb.factors(x, None).0;
+            let result = annotated(x);
+            assert!(result.iter().all(|(k, &(e, _))| expected[k] == e));
+            assert_eq!(result.len(), expected.len());
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p = next_prime(&BigUint::from(1u64 << 24), None).unwrap();
+            let q = next_prime(&(BigUint::from(1u8) << 110u32), None).unwrap();
+            let n = &p * &q * 12u8;
+            let result = pb.factors_annotated(n.clone(), None).unwrap();
+            assert_eq!(result[&BigUint::from(2u8)], (2, TrialDivision));
+            assert_eq!(result[&p], (1, PollardRho));
+            assert_eq!(result[&q], (1, PollardRho));
+            let result = pb.factors_annotated(&q * &q * 12u8, None).unwrap();
+            assert_eq!(result[&q], (2, PerfectPower));
+
+            let mut config = FactorizationConfig::default();
+            config.rho_trials = 0;
+            let pq = &p * &q;
+            let result = pb.factors_annotated(pq.clone(), Some(config));
+            assert_eq!(result, Err(vec![pq]));
+        }
+    }
+
+    #[test]
+    fn factors_with_timeout_test() {
+        use std::time::Duration;
+
+        let pb = NaiveBuffer::new();
+        let past = Instant::now() - Duration::from_millis(1);
+        let target = 1000000007u128 * 998244353 * 65537;
+        let (result, cofactor) = pb.factors_with_timeout(target, None, past);
+        assert!(result.is_empty());
+        assert_eq!(cofactor, Some(target));
+
+        let future = Instant::now() + Duration::from_secs(60);
+        let (result, cofactor) = pb.factors_with_timeout(target, None, future);
+        assert_eq!(cofactor, None);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[&998244353], 1);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            // a product of two 128-bit primes can't be factored in a short time
+            let p = BigUint::from_str("340282366920938463463374607431768211297").unwrap();
+            let q = BigUint::from_str("340282366920938463463374607431768211283").unwrap();
+            let target = &p * &q * BigUint::from(6u8);
+            let started = Instant::now();
+            let deadline = started + Duration::from_millis(100);
+            let (result, cofactor) = pb.factors_with_timeout(target, None, deadline);
+            assert!(started.elapsed() < Duration::from_secs(1));
+            assert_eq!(result.len(), 2);
+            assert_eq!(cofactor, Some(p * q));
+        }
+    }
+
+    #[test]
+    fn divisor_binary_gcd_test() {
+        let pb = NaiveBuffer::new();
+        let mut config = FactorizationConfig::default();
+        config.binary_gcd = true;
+        let (p, q) = (1000000007u128, 998244353u128);
+        let d = pb.divisor(&(p * q), &mut config).unwrap();
+        assert!(d == p || d == q);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p = BigUint::from(18446744073709551557u64);
+            let q = BigUint::from(4294967291u32);
+            let mut config = FactorizationConfig::default();
+            config.binary_gcd = true;
+            let (factors, rest) = pb.factors(&p * &q * &q, Some(config));
+            assert!(rest.is_none());
+            assert_eq!(factors, BTreeMap::from([(p, 1), (q, 2)]));
+        }
+    }
+
+    #[test]
+    fn factors_square_test() {
+        let pb = NaiveBuffer::new();
+        let x = 104729u64 * 104729;
+        let (result, failed) = pb.factors(x, None);
+        assert!(failed.is_none());
+        assert_eq!(result, BTreeMap::from([(104729, 2)]));
+
+        #[cfg(feature = "num-bigint")]
+        {
+            // squares beyond 2^128 used to be left unfactored
+            let p = BigUint::from(2u8).pow(89) - 1u8;
+            let (result, failed) = pb.factors(&p * &p * 3u8, None);
+            assert!(failed.is_none());
+            assert_eq!(result[&p], 2);
+            let (result, failed) = pb.factors(p.pow(4), None);
+            assert!(failed.is_none());
+            assert_eq!(result[&p], 4);
+        }
+    }
+
     #[test]
     fn pb_factors_test() {
         let pb = NaiveBuffer::new();
@@ -82,6 +82,31 @@ pub fn pollard_rho<
     offset: T,
     max_iter: usize,
 ) -> (Option<T>, usize)
+where
+    for<'r> &'r T: RefNum<T>,
+{
+    pollard_rho_with(target, start, offset, max_iter, |a, b| a.gcd(b), || false)
+}
+
+/// Same as [pollard_rho], but the gcd steps use the given `gcd` function, and `interrupted` is
+/// polled every 1024 iterations and the search is stopped once it returns true.
+pub(crate) fn pollard_rho_with<
+    T: Integer
+        + FromPrimitive
+        + NumRef
+        + Clone
+        + for<'r> ModularCoreOps<&'r T, &'r T, Output = T>
+        + for<'r> ModularUnaryOps<&'r T, Output = T>,
+    G: Fn(&T, &T) -> T,
+    F: FnMut() -> bool,
+>(
+    target: &T,
+    start: T,
+    offset: T,
+    max_iter: usize,
+    gcd: G,
+    mut interrupted: F,
+) -> (Option<T>, usize)
 where
     for<'r> &'r T: RefNum<T>,
 {
@@ -97,6 +122,9 @@ where
     let mut backtrace = false;
 
     while i < max_iter {
+        if i & 1023 == 0 && interrupted() {
+            return (None, i);
+        }
         i += 1;
         a = a.sqm(&target).addm(&offset, &target);
         if a == b {
@@ -123,7 +151,7 @@ where
         // larger batch size leads to large overhead when backtracing.
         // reference: https://www.cnblogs.com/812-xiao-wen/p/10544546.html
         if i == j || i & 127 == 0 || backtrace {
-            let d = z.gcd(target);
+            let d = gcd(&z, target);
             if !d.is_one() && &d != target {
                 return (Some(d), i);
             }
@@ -150,6 +178,8 @@ where
 ///
 /// The max iteration can be choosed as 2*n^(1/4), based on Theorem 4.22 from [1].
 ///
+/// See [squfof_full][crate::nt_funcs::squfof_full] for a version that tries a list of multipliers.
+///
 /// Reference: Gower, J., & Wagstaff Jr, S. (2008). Square form factorization.
 /// In [1] [Mathematics of Computation](https://homes.cerias.purdue.edu/~ssw/gowerthesis804/wthe.pdf)
 /// or [2] [his thesis](https://homes.cerias.purdue.edu/~ssw/gowerthesis804/wthe.pdf)
@@ -73,6 +73,7 @@ mod rand;
 mod tables;
 mod traits;
 
+pub use mint::{FixedBaseExp, Modulus};
 pub use traits::*;
 pub mod detail {
     //! Implementation details for this crate.
@@ -8,8 +8,9 @@ use num_modular::{
     ModularCoreOps, ModularInteger, ModularPow, ModularSymbols, ModularUnaryOps, Montgomery,
     ReducedInt, Reducer,
 };
-use num_traits::{FromPrimitive, Num, One, Pow, ToPrimitive, Zero};
+use num_traits::{FromPrimitive, Inv, Num, One, Pow, ToPrimitive, Zero};
 
+use crate::primality::{PrimalityBase, PrimalityRefBase};
 use crate::{BitTest, ExactRoots};
 
 /// Integer with fast modular arithmetics support, based on [MontgomeryInt] under the hood
@@ -124,6 +125,27 @@ impl<T: Integer + Clone, R: Reducer<T> + Clone> Mint<T, R> {
             Right(m) => m.residue(),
         }
     }
+
+    /// Modular division `self / rhs (mod m)`, i.e. the multiplication with the modular inverse
+    /// of `rhs`. Returns [None] if `rhs` is not invertible modulo `m`.
+    pub fn divm(&self, rhs: &Self, m: &Self) -> Option<Self> {
+        let m = match &m.0 {
+            Left(m) => m,
+            Right(_) => unreachable!(),
+        };
+        let reduce = |v: &Self| match &v.0 {
+            Left(v) => ReducedInt::new(v.clone(), m),
+            Right(v) => {
+                debug_assert!(&v.modulus() == m);
+                v.clone()
+            }
+        };
+        let rhs = reduce(rhs);
+        if !rhs.residue().gcd(m).is_one() {
+            return None;
+        }
+        Some(Self(Right(reduce(self) * rhs.inv())))
+    }
 }
 
 // forward binary operators by converting result to MontgomeryInt whenever possible
@@ -681,6 +703,230 @@ impl<T: Integer + Clone, R: Reducer<T> + Clone> ModularPow<&Self, &Self> for Min
 
 pub type SmallMint<T> = Mint<T, Montgomery<T, T>>;
 
+/// Barrett reduction for a modulus `m < 2^64`, with the precomputed `r = floor((2^128 - 1) / m)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Barrett64 {
+    m: u64,
+    r: u128,
+}
+
+impl Barrett64 {
+    fn new(m: u64) -> Self {
+        debug_assert!(m > 1);
+        Self {
+            m,
+            r: u128::MAX / m as u128,
+        }
+    }
+
+    /// Get the high 128 bits of the 256-bit product `a * b`
+    #[inline]
+    fn mul_high(a: u128, b: u128) -> u128 {
+        const MASK: u128 = u64::MAX as u128;
+        let (a1, a0, b1, b0) = (a >> 64, a & MASK, b >> 64, b & MASK);
+        let (lo, mid1, mid2) = (a0 * b0, a1 * b0, a0 * b1);
+        let carry = ((lo >> 64) + (mid1 & MASK) + (mid2 & MASK)) >> 64;
+        a1 * b1 + (mid1 >> 64) + (mid2 >> 64) + carry
+    }
+
+    /// Calculate `x mod m` by multiply-and-shift. The estimated quotient is at most
+    /// 2 less than the real one, so at most two corrections are needed.
+    #[inline]
+    fn reduce(&self, x: u128) -> u64 {
+        let q = Self::mul_high(x, self.r);
+        let mut rem = x - q * self.m as u128;
+        while rem >= self.m as u128 {
+            rem -= self.m as u128;
+        }
+        rem as u64
+    }
+
+    #[inline]
+    fn mulm(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    fn powm(&self, base: u64, mut exp: u64) -> u64 {
+        let (mut result, mut b) = (1 % self.m, base);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mulm(result, b);
+            }
+            b = self.mulm(b, b);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+/// Modular arithmetic in the ring of integers modulo a fixed modulus.
+///
+/// The modulus is validated once when the struct is created, and it doesn't need to be passed
+/// to every operation. The operands of the arithmetic methods are expected to be already
+/// reduced, see [Modulus::reduce].
+///
+/// For moduli fitting in 64 bits, the constant for Barrett reduction is precomputed, so that
+/// the multiplications are reduced by multiply-and-shift instead of the 128-bit division.
+/// Unlike Montgomery multiplication, it also works for even moduli.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modulus<T> {
+    m: T,
+    barrett: Option<Barrett64>,
+}
+
+impl<T: PrimalityBase> Modulus<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    /// Create the ring of integers modulo m
+    ///
+    /// # Panics
+    /// if m is zero.
+    pub fn new(m: T) -> Self {
+        assert!(!m.is_zero(), "the modulus should be positive");
+        let barrett = m.to_u64().filter(|&v| v > 1).map(Barrett64::new);
+        Self { m, barrett }
+    }
+
+    /// Get the modulus
+    #[inline]
+    pub fn modulus(&self) -> &T {
+        &self.m
+    }
+
+    /// Reduce an arbitrary integer into the range `[0, m)`
+    #[inline]
+    pub fn reduce(&self, a: &T) -> T {
+        a % &self.m
+    }
+
+    /// Calculate `(a + b) mod m`
+    #[inline]
+    pub fn add(&self, a: &T, b: &T) -> T {
+        a.addm(b, &self.m)
+    }
+
+    /// Calculate `(a - b) mod m`
+    #[inline]
+    pub fn sub(&self, a: &T, b: &T) -> T {
+        a.subm(b, &self.m)
+    }
+
+    /// Calculate `-a mod m`
+    #[inline]
+    pub fn neg(&self, a: &T) -> T {
+        a.negm(&self.m)
+    }
+
+    /// Calculate `(a * b) mod m`
+    #[inline]
+    pub fn mul(&self, a: &T, b: &T) -> T {
+        if let (Some(br), Some(a64), Some(b64)) = (&self.barrett, a.to_u64(), b.to_u64()) {
+            return T::from_u64(br.mulm(a64, b64)).unwrap();
+        }
+        a.mulm(b, &self.m)
+    }
+
+    /// Calculate `base^exp mod m`
+    #[inline]
+    pub fn pow(&self, base: &T, exp: &T) -> T {
+        if let (Some(br), Some(b64), Some(e64)) = (&self.barrett, base.to_u64(), exp.to_u64()) {
+            return T::from_u64(br.powm(b64, e64)).unwrap();
+        }
+        base.clone().powm(exp, &self.m)
+    }
+
+    /// Calculate the modular inverse of a, returns [None] if a is not coprime to m
+    #[inline]
+    pub fn inv(&self, a: &T) -> Option<T> {
+        a.invm(&self.m)
+    }
+
+    /// Calculate `a * b^-1 mod m`, returns [None] if b is not coprime to m.
+    /// See [divm][crate::nt_funcs::divm].
+    #[inline]
+    pub fn div(&self, a: &T, b: &T) -> Option<T> {
+        crate::nt_funcs::divm(a, b, &self.m)
+    }
+}
+
+/// Window size of the precomputed table in [FixedBaseExp]
+const FIXED_BASE_WINDOW: usize = 5;
+
+/// Modular exponentiation with a fixed base and modulus, using a precomputed table of the odd
+/// powers of the base for the sliding window method. Created by
+/// [powm_fixed_base()][crate::nt_funcs::powm_fixed_base].
+///
+/// Compared to the binary method, the sliding window method reduces the number of
+/// multiplications from about `bits/2` to `bits/(w+1)`, where `w` is the window size.
+#[derive(Debug, Clone)]
+pub struct FixedBaseExp<T> {
+    m: T,
+    /// base^1, base^3, ..., base^(2^w - 1) mod m
+    table: Vec<T>,
+}
+
+impl<T: PrimalityBase> FixedBaseExp<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    /// Precompute the table for the base modulo m
+    ///
+    /// # Panics
+    /// if m is zero.
+    pub fn new(base: &T, m: &T) -> Self {
+        assert!(!m.is_zero(), "the modulus should be positive");
+        let b = base % m;
+        let b2 = (&b).mulm(&b, m);
+        let mut table = Vec::with_capacity(1 << (FIXED_BASE_WINDOW - 1));
+        table.push(b);
+        for i in 1..table.capacity() {
+            let next = (&table[i - 1]).mulm(&b2, m);
+            table.push(next);
+        }
+        Self {
+            m: m.clone(),
+            table,
+        }
+    }
+
+    /// Get the modulus
+    #[inline]
+    pub fn modulus(&self) -> &T {
+        &self.m
+    }
+
+    /// Calculate `base^exp mod m`
+    pub fn pow(&self, exp: &T) -> T {
+        let m = &self.m;
+        let mut result = T::one() % m;
+
+        // scan the exponent from the most significant bit
+        let mut i = exp.bits();
+        while i > 0 {
+            if !exp.bit(i - 1) {
+                result = (&result).mulm(&result, m);
+                i -= 1;
+                continue;
+            }
+
+            // find the longest window ending with a set bit
+            let mut j = i.saturating_sub(FIXED_BASE_WINDOW);
+            while !exp.bit(j) {
+                j += 1;
+            }
+            let mut window = 0;
+            for k in (j..i).rev() {
+                result = (&result).mulm(&result, m);
+                window = (window << 1) | exp.bit(k) as usize;
+            }
+            result = (&result).mulm(&self.table[window >> 1], m);
+            i = j;
+        }
+        result
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -691,4 +937,103 @@ mod tests {
         let b: SmallMint<u32> = 8.into();
         assert_eq!(a + b, 27.into());
     }
+
+    #[test]
+    fn divm_test() {
+        let m = 1000000007u64;
+        let mm = SmallMint::from(m);
+        for _ in 0..100 {
+            let (a, b) = (rand::random::<u64>() % m, rand::random::<u64>() % m);
+            let expected = b.invm(&m).map(|bi| a.mulm(bi, &m));
+            let (sa, sb) = (SmallMint::from(a), SmallMint::from(b));
+            assert_eq!(sa.divm(&sb, &mm).map(|v| v.value()), expected);
+
+            // operands in montgomery form
+            let (sa, sb) = (sa % &mm, sb % &mm);
+            assert_eq!(sa.divm(&sb, &mm).map(|v| v.value()), expected);
+        }
+
+        let mm = SmallMint::from(15u32);
+        let (a, b) = (SmallMint::from(7u32), SmallMint::from(4u32));
+        assert_eq!(a.divm(&b, &mm).map(|v| v.value()), Some(13));
+        assert_eq!(a.divm(&SmallMint::from(6), &mm), None);
+        assert_eq!(a.divm(&SmallMint::from(0), &mm), None);
+    }
+
+    #[test]
+    fn fixed_base_exp_test() {
+        for &m in [1u64, 2, 1000000007, u64::MAX, rand::random()].iter() {
+            let base: u64 = rand::random();
+            let fixed = FixedBaseExp::new(&base, &m);
+            assert_eq!(fixed.modulus(), &m);
+            for e in (0..100).chain((0..100).map(|_| rand::random())) {
+                assert_eq!(fixed.pow(&e), base.powm(e, &m), "{}^{} mod {}", base, e, m);
+            }
+        }
+        assert_eq!(FixedBaseExp::new(&0u32, &7).pow(&0), 1);
+        assert_eq!(FixedBaseExp::new(&3u8, &255).pow(&255), 3u8.powm(255, &255));
+
+        #[cfg(feature = "num-bigint")]
+        {
+            use num_bigint::BigUint;
+            let m = (BigUint::from(1u8) << 521u32) - BigUint::from(1u8);
+            let base = BigUint::from(3u8);
+            let fixed = FixedBaseExp::new(&base, &m);
+            for _ in 0..5 {
+                let e = BigUint::from(rand::random::<u128>()) << 200u32;
+                assert_eq!(fixed.pow(&e), base.modpow(&e, &m));
+            }
+        }
+    }
+
+    #[test]
+    fn modulus_test() {
+        let m = 1000000007u64;
+        let ring = Modulus::new(m);
+        assert_eq!(ring.modulus(), &m);
+        for _ in 0..100 {
+            let (a, b) = (ring.reduce(&rand::random()), ring.reduce(&rand::random()));
+            let e: u64 = rand::random();
+            assert_eq!(ring.add(&a, &b), a.addm(b, &m));
+            assert_eq!(ring.sub(&a, &b), a.subm(b, &m));
+            assert_eq!(ring.neg(&a), a.negm(&m));
+            assert_eq!(ring.mul(&a, &b), a.mulm(b, &m));
+            assert_eq!(ring.pow(&a, &e), a.powm(e, &m));
+            assert_eq!(ring.inv(&a), a.invm(&m));
+            assert_eq!(ring.div(&b, &a), a.invm(&m).map(|ai| b.mulm(ai, &m)));
+        }
+        assert_eq!(ring.div(&1, &0), None);
+        assert_eq!(Modulus::new(10u32).div(&7, &4), None);
+        assert_eq!(Modulus::new(10u32).div(&17, &3), Some(9));
+        assert_eq!(ring.inv(&0), None);
+        assert_eq!(Modulus::new(1u64).add(&0, &0), 0);
+
+        // Barrett reduction against the division based reduction, with even and extreme moduli
+        let moduli = [2u64, 3, 1 << 32, (1 << 63) + 1, u64::MAX, rand::random()];
+        for &m in moduli.iter().filter(|&&m| m > 1) {
+            let ring = Modulus::new(m);
+            for (a, b) in [(m - 1, m - 1), (0, m - 1), (1, m - 1)] {
+                assert_eq!(ring.mul(&a, &b), a.mulm(b, &m));
+            }
+            for _ in 0..1000 {
+                let (a, b) = (ring.reduce(&rand::random()), ring.reduce(&rand::random()));
+                assert_eq!(ring.mul(&a, &b), a.mulm(b, &m));
+            }
+            let (a, e): (u64, u64) = (ring.reduce(&rand::random()), rand::random());
+            assert_eq!(ring.pow(&a, &e), a.powm(e, &m));
+        }
+        assert_eq!(Modulus::new(1u64).pow(&0, &0), 0);
+        assert_eq!(Modulus::new(5u128).mul(&4, &4), 1);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            use num_bigint::BigUint;
+            let m = (BigUint::from(1u8) << 127u8) - BigUint::from(1u8);
+            let ring = Modulus::new(m.clone());
+            let (a, b) = (BigUint::from(u64::MAX), BigUint::from(12345u32));
+            assert_eq!(ring.mul(&a, &b), (&a).mulm(&b, &m));
+            assert_eq!(ring.pow(&a, &b), a.clone().powm(&b, &m));
+            assert_eq!(ring.mul(&a, &ring.inv(&a).unwrap()), BigUint::from(1u8));
+        }
+    }
 }
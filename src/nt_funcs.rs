@@ -11,86 +11,146 @@
 //! over speed.
 //!
 
-use crate::buffer::{NaiveBuffer, PrimeBufferExt};
+use crate::buffer::{random_below, NaiveBuffer, PrimeBufferExt};
 use crate::factor::{one_line, pollard_rho, squfof, SQUFOF_MULTIPLIERS};
-use crate::mint::SmallMint;
-use crate::primality::{PrimalityBase, PrimalityRefBase};
+use crate::mint::{FixedBaseExp, SmallMint};
+use crate::primality::{LucasUtils, PrimalityBase, PrimalityRefBase};
 use crate::tables::{
     MOEBIUS_ODD, SMALL_PRIMES, SMALL_PRIMES_NEXT, WHEEL_NEXT, WHEEL_PREV,
     WHEEL_SIZE,
 };
 #[cfg(feature = "big-table")]
 use crate::tables::{SMALL_PRIMES_INV, ZETA_LOG_TABLE};
-use crate::traits::{FactorizationConfig, Primality, PrimalityTestConfig, PrimalityUtils};
+use crate::traits::{
+    FactorDifficulty, FactorMethod, FactorTree, FactorizationConfig, ModularPrimitives, Primality,
+    PrimalityTestConfig, PrimalityUtils, PrimeBuffer,
+};
 use crate::{BitTest, ExactRoots};
 use num_integer::Roots;
 #[cfg(feature = "num-bigint")]
+use num_bigint::{BigInt, BigUint, ParseBigIntError, Sign};
+#[cfg(feature = "num-bigint")]
 use num_modular::DivExact;
-use num_modular::{ModularCoreOps, ModularInteger, MontgomeryInt};
-use num_traits::{CheckedAdd, FromPrimitive, Num, RefNum, ToPrimitive};
-use rand::random;
-use std::collections::BTreeMap;
+use num_modular::{
+    ModularCoreOps, ModularInteger, ModularPow, ModularSymbols, ModularUnaryOps, MontgomeryInt,
+};
+use num_traits::{CheckedAdd, CheckedMul, FromPrimitive, Num, RefNum, ToPrimitive};
+use rand::{random, Rng};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BinaryHeap};
 use std::convert::TryFrom;
 
 #[cfg(feature = "big-table")]
 use crate::tables::{MILLER_RABIN_BASE64, MILLER_RABIN_BASE32};
 
-/// Fast primality test on a u64 integer. It's based on
-/// deterministic Miller-rabin tests. If target is larger than 2^64 or more
-/// controlled primality tests are desired, please use [is_prime()]
-#[cfg(not(feature = "big-table"))]
+/// Fast primality test on a u64 integer. It's based on deterministic Miller-rabin tests,
+/// with hashing of the witnesses when the `big-table` feature is enabled. If target is larger
+/// than 2^64 or more controlled primality tests are desired, please use [is_prime()]
 pub fn is_prime64(target: u64) -> bool {
-    // shortcuts
-    if target < 2 {
-        return false;
+    is_prime64_explain(target).0
+}
+
+/// Bit mask of the quadratic residues modulo m (m <= 128)
+const fn square_residue_mask(m: u64) -> u128 {
+    let mut mask = 0u128;
+    let mut i = 0;
+    while i < m {
+        mask |= 1 << (i * i % m);
+        i += 1;
     }
-    if target & 1 == 0 {
-        return target == 2;
+    mask
+}
+
+const SQUARE_MASK_64: u128 = square_residue_mask(64);
+const SQUARE_MASK_63: u128 = square_residue_mask(63);
+const SQUARE_MASK_65: u128 = square_residue_mask(65);
+const SQUARE_MASK_11: u128 = square_residue_mask(11);
+
+/// Test if an integer is a perfect square.
+///
+/// Most non-squares are eliminated by checking the quadratic residues modulo 64, 63, 65 and 11
+/// before the exact square root is calculated.
+pub fn is_perfect_square<T: PrimalityBase>(target: &T) -> bool
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    const MODULUS: u32 = 64 * 63 * 65 * 11;
+    if target.is_zero() {
+        return true;
     }
-    if let Ok(u) = u8::try_from(target) {
-        // find in the prime list if the target is small enough
-        return SMALL_PRIMES.binary_search(&u).is_ok();
-    } else {
-        // check remainder against the wheel table
-        // this step eliminates any number that is not coprime to WHEEL_SIZE
-        let pos = (target % WHEEL_SIZE as u64) as usize;
-        if pos == 0 || WHEEL_NEXT[pos] < WHEEL_NEXT[pos - 1] {
+
+    // the modulus doesn't fit in small integer types, reduce the target as u64 instead
+    let r = match T::from_u32(MODULUS) {
+        Some(m) => (target % m).to_u64(),
+        None => target.to_u64().map(|v| v % MODULUS as u64),
+    };
+    if let Some(r) = r {
+        let is_residue = SQUARE_MASK_64 & (1u128 << (r % 64)) != 0
+            && SQUARE_MASK_63 & (1u128 << (r % 63)) != 0
+            && SQUARE_MASK_65 & (1u128 << (r % 65)) != 0
+            && SQUARE_MASK_11 & (1u128 << (r % 11)) != 0;
+        if !is_residue {
             return false;
         }
     }
-
-    // Then do a deterministic Miller-rabin test
-    is_prime64_miller(target)
+    target.is_square()
 }
 
-/// Very fast primality test on a u64 integer is a prime number. It's based on
-/// deterministic Miller-rabin tests with hashing. if target is larger than 2^64 or more controlled
-/// primality tests are desired, please use [is_prime()]
-#[cfg(feature = "big-table")]
-pub fn is_prime64(target: u64) -> bool {
-    // shortcuts
-    if target < 2 {
-        return false;
+/// Deterministic primality test on a u64 integer that behaves the same regardless of the
+/// `big-table` feature. After trial division by small primes, it runs Miller-Rabin tests with
+/// the minimal known base set for the range of the target (from <https://miller-rabin.appspot.com/>
+/// and <https://oeis.org/A014233>), so no large hash table is required.
+///
+/// [is_prime64] is usually faster when the `big-table` feature is enabled.
+pub fn is_prime64_deterministic(target: u64) -> bool {
+    // trial division with primes up to 73
+    for &p in SMALL_PRIMES[..21].iter() {
+        let p = p as u64;
+        if target.is_multiple_of(p) {
+            return target == p;
+        }
     }
-    if target & 1 == 0 {
-        return target == 2;
+    if target < 73 * 73 {
+        return target > 1;
     }
 
-    // remove small factors
+    // all the bases are smaller than the targets in their ranges
+    let bases: &[u64] = match target {
+        0..=2046 => &[2],
+        2047..=1373652 => &[2, 3],
+        1373653..=9080190 => &[31, 73],
+        9080191..=4759123140 => &[2, 7, 61],
+        4759123141..=1122004669632 => &[2, 13, 23, 1662803],
+        1122004669633..=3474749660382 => &[2, 3, 5, 7, 11, 13],
+        3474749660383..=341550071728320 => &[2, 3, 5, 7, 11, 13, 17],
+        _ => &[2, 325, 9375, 28178, 450775, 9780504, 1795265022],
+    };
+    let u = SmallMint::from(target);
+    bases.iter().all(|&b| u.is_sprp(SmallMint::from(b)))
+}
+
+/// Same as [is_prime64], but also returns the name of the decision path,
+/// see [PrimeBufferExt::is_prime_explain()] for the possible values.
+pub(crate) fn is_prime64_explain(target: u64) -> (bool, &'static str) {
+    // shortcuts
+    if target < 2 || target & 1 == 0 {
+        return (target == 2, "trivial");
+    }
     if target < SMALL_PRIMES_NEXT {
         // find in the prime list if the target is small enough
-        return SMALL_PRIMES.binary_search(&(target as u16)).is_ok();
-    } else {
-        // check remainder against the wheel table
-        // this step eliminates any number that is not coprime to WHEEL_SIZE
-        let pos = (target % WHEEL_SIZE as u64) as usize;
-        if pos == 0 || WHEEL_NEXT[pos] < WHEEL_NEXT[pos - 1] {
-            return false;
-        }
+        let found = SMALL_PRIMES.binary_search(&(target as _)).is_ok();
+        return (found, "small-prime-table");
+    }
+
+    // check remainder against the wheel table
+    // this step eliminates any number that is not coprime to WHEEL_SIZE
+    let pos = (target % WHEEL_SIZE as u64) as usize;
+    if pos == 0 || WHEEL_NEXT[pos] < WHEEL_NEXT[pos - 1] {
+        return (false, "trial-division-composite");
     }
 
     // Then do a deterministic Miller-rabin test
-    is_prime64_miller(target)
+    (is_prime64_miller(target), "miller-rabin-deterministic-u64")
 }
 
 // Primality test for u64 with only miller-rabin tests, used during factorization.
@@ -145,7 +205,29 @@ fn is_prime64_miller(target: u64) -> bool {
 ///
 /// The factorization can be quite faster under 2^64 because: 1) faster and deterministic primality check,
 /// 2) efficient montgomery multiplication implementation of u64
+///
+/// The random numbers used by the factorization methods are drawn from [rand::thread_rng()],
+/// see [factorize64_with_rng()] for using another random source.
 pub fn factorize64(target: u64) -> BTreeMap<u64, usize> {
+    factorize64_with_rng(target, &mut rand::thread_rng())
+}
+
+/// Fast integer factorization on a u64 target, same as [factorize64()] but the random numbers
+/// (e.g. the starting points of Pollard's rho method) are drawn from the given random source.
+///
+/// A seeded random generator makes the factorization steps reproducible.
+pub fn factorize64_with_rng<R: Rng + ?Sized>(target: u64, rng: &mut R) -> BTreeMap<u64, usize> {
+    factorize64_annotated(target, rng)
+        .into_iter()
+        .map(|(p, (exp, _))| (p, exp))
+        .collect()
+}
+
+// Factorize a u64 target like factorize64_with_rng(), with the method that found each prime factor
+pub(crate) fn factorize64_annotated<R: Rng + ?Sized>(
+    target: u64,
+    rng: &mut R,
+) -> BTreeMap<u64, (usize, FactorMethod)> {
     // TODO: improve factorization performance
     // REF: http://flintlib.org/doc/ulong_extras.html#factorisation
     //      https://mathoverflow.net/questions/114018/fastest-way-to-factor-integers-260
@@ -162,11 +244,11 @@ pub fn factorize64(target: u64) -> BTreeMap<u64, usize> {
     let f2 = target.trailing_zeros();
     if f2 == 0 {
         if is_prime64(target) {
-            result.insert(target, 1);
+            result.insert(target, (1, FactorMethod::Prime));
             return result;
         }
     } else {
-        result.insert(2, f2 as usize);
+        result.insert(2, (f2 as usize, FactorMethod::TrialDivision));
     }
 
     // trial division using primes in the table
@@ -182,9 +264,13 @@ pub fn factorize64(target: u64) -> BTreeMap<u64, usize> {
             break;
         }
 
+        let mut exp: usize = 0;
         while residual % p == 0 {
             residual = residual / p;
-            *result.entry(p).or_insert(0) += 1;
+            exp += 1;
+        }
+        if exp > 0 {
+            result.insert(p, (exp, FactorMethod::TrialDivision));
         }
         if residual == 1 {
             factored = true;
@@ -212,7 +298,7 @@ pub fn factorize64(target: u64) -> BTreeMap<u64, usize> {
             residual = q;
         }
         if exp > 0 {
-            result.insert(p, exp);
+            result.insert(p, (exp, FactorMethod::TrialDivision));
         }
 
         if residual == 1 {
@@ -223,26 +309,35 @@ pub fn factorize64(target: u64) -> BTreeMap<u64, usize> {
 
     if factored {
         if residual != 1 {
-            result.insert(residual, 1);
+            result.insert(residual, (1, FactorMethod::TrialDivision));
         }
         return result;
     }
 
     // then try advanced methods to find a divisor util fully factored
-    for (p, exp) in factorize64_advanced(&[(residual, 1usize)]).into_iter() {
-        *result.entry(p).or_insert(0) += exp;
+    // the cofactor is the target itself if no small factor is found
+    let method = match result.is_empty() {
+        true => FactorMethod::Prime,
+        false => FactorMethod::TrialDivision,
+    };
+    for (p, exp, m) in factorize64_advanced(&[(residual, 1, method)], rng).into_iter() {
+        result.entry(p).or_insert((0, m)).0 += exp;
     }
     result
 }
 
-// This function factorize all cofactors after some trivial division steps
-pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usize)> {
+// This function factorize all cofactors after some trivial division steps, each cofactor
+// is tagged with the method that found it, and the tag is passed to its prime factors
+pub(crate) fn factorize64_advanced<R: Rng + ?Sized>(
+    cofactors: &[(u64, usize, FactorMethod)],
+    rng: &mut R,
+) -> Vec<(u64, usize, FactorMethod)> {
     let mut todo: Vec<_> = cofactors.iter().cloned().collect();
-    let mut factored: Vec<(u64, usize)> = Vec::new(); // prime factor, exponent
+    let mut factored: Vec<(u64, usize, FactorMethod)> = Vec::new(); // prime factor, exponent, method
 
-    while let Some((target, exp)) = todo.pop() {
+    while let Some((target, exp, method)) = todo.pop() {
         if is_prime64_miller(target) {
-            factored.push((target, exp));
+            factored.push((target, exp, method));
             continue;
         }
 
@@ -250,25 +345,25 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
         // it suffices to check square and cubic if big-table is enabled, since fifth power of
         // the smallest prime that haven't been checked is 8167^5 > 2^64
         if let Some(d) = target.sqrt_exact() {
-            todo.push((d, exp * 2));
+            todo.push((d, exp * 2, FactorMethod::PerfectPower));
             continue;
         }
         if let Some(d) = target.cbrt_exact() {
-            todo.push((d, exp * 3));
+            todo.push((d, exp * 3, FactorMethod::PerfectPower));
             continue;
         }
 
         // try to find a divisor
         let mut i = 0usize;
         let mut max_iter_ratio = 1; // increase max_iter after factorization round
-        let divisor = loop {
+        let (divisor, method) = loop {
             // try various factorization method iteratively
             const NMETHODS: usize = 3;
             match i % NMETHODS {
                 0 => {
                     // Pollard's rho (quick check)
-                    let start = MontgomeryInt::new(random::<u64>(), &target);
-                    let offset = start.convert(random::<u64>());
+                    let start = MontgomeryInt::new(rng.gen::<u64>(), &target);
+                    let offset = start.convert(rng.gen::<u64>());
                     let max_iter = max_iter_ratio << (target.bits() / 6); // unoptimized heuristic
                     if let (Some(p), _) = pollard_rho(
                         &SmallMint::from(target),
@@ -276,7 +371,7 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
                         offset.into(),
                         max_iter,
                     ) {
-                        break p.value();
+                        break (p.value(), FactorMethod::PollardRho);
                     }
                 }
                 1 => {
@@ -284,7 +379,7 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
                     let mul_target = target.checked_mul(480).unwrap_or(target);
                     let max_iter = max_iter_ratio << (mul_target.bits() / 6); // unoptimized heuristic
                     if let (Some(p), _) = one_line(&target, mul_target, max_iter) {
-                        break p;
+                        break (p, FactorMethod::OneLine);
                     }
                 }
                 2 => {
@@ -292,6 +387,15 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
                     let mut d = None;
                     for &k in SQUFOF_MULTIPLIERS.iter() {
                         if let Some(mul_target) = target.checked_mul(k as u64) {
+                            // SQUFOF can't proceed on a square, extract the factor directly
+                            if is_perfect_square(&mul_target) {
+                                let g = num_integer::gcd(target, mul_target.sqrt());
+                                if g > 1 && g < target {
+                                    d = Some(g);
+                                    break;
+                                }
+                                continue;
+                            }
                             let max_iter = max_iter_ratio * 2 * mul_target.sqrt().sqrt() as usize;
                             if let (Some(p), _) = squfof(&target, mul_target, max_iter) {
                                 d = Some(p);
@@ -300,7 +404,7 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
                         }
                     }
                     if let Some(p) = d {
-                        break p;
+                        break (p, FactorMethod::Squfof);
                     }
                 }
                 _ => unreachable!(),
@@ -312,8 +416,8 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
                 max_iter_ratio *= 2;
             }
         };
-        todo.push((divisor, exp));
-        todo.push((target / divisor, exp));
+        todo.push((divisor, exp, method));
+        todo.push((target / divisor, exp, method));
     }
     factored
 }
@@ -321,9 +425,17 @@ pub(crate) fn factorize64_advanced(cofactors: &[(u64, usize)]) -> Vec<(u64, usiz
 /// Fast integer factorization on a u128 target. It's based on a selection of factorization methods.
 /// if target is larger than 2^128 or more controlled primality tests are desired, please use [factors()][crate::buffer::PrimeBufferExt::factors].
 pub fn factorize128(target: u128) -> BTreeMap<u128, usize> {
+    factorize128_annotated(target)
+        .into_iter()
+        .map(|(p, (exp, _))| (p, exp))
+        .collect()
+}
+
+// Factorize a u128 target like factorize128(), with the method that found each prime factor
+pub(crate) fn factorize128_annotated(target: u128) -> BTreeMap<u128, (usize, FactorMethod)> {
     // shortcut for u64
     if target < (1u128 << 64) {
-        return factorize64(target as u64)
+        return factorize64_annotated(target as u64, &mut rand::thread_rng())
             .into_iter()
             .map(|(k, v)| (k as u128, v))
             .collect();
@@ -334,7 +446,7 @@ pub fn factorize128(target: u128) -> BTreeMap<u128, usize> {
     // quick check on factors of 2
     let f2 = target.trailing_zeros();
     if f2 != 0 {
-        result.insert(2, f2 as usize);
+        result.insert(2, (f2 as usize, FactorMethod::TrialDivision));
     }
     let mut residual = target >> f2;
 
@@ -342,9 +454,13 @@ pub fn factorize128(target: u128) -> BTreeMap<u128, usize> {
     // note that p^2 is never larger than target (at least 64 bits), so we don't need to shortcut trial division
     #[cfg(not(feature = "big-table"))]
     for p in SMALL_PRIMES.iter().skip(1).map(|&v| v as u128) {
+        let mut exp: usize = 0;
         while residual % p == 0 {
             residual = residual / p;
-            *result.entry(p).or_insert(0) += 1;
+            exp += 1;
+        }
+        if exp > 0 {
+            result.insert(p, (exp, FactorMethod::TrialDivision));
         }
         if residual == 1 {
             return result;
@@ -365,7 +481,7 @@ pub fn factorize128(target: u128) -> BTreeMap<u128, usize> {
             residual = q;
         }
         if exp > 0 {
-            result.insert(p as u128, exp);
+            result.insert(p as u128, (exp, FactorMethod::TrialDivision));
         }
 
         if residual == 1 {
@@ -374,26 +490,33 @@ pub fn factorize128(target: u128) -> BTreeMap<u128, usize> {
     }
 
     // then try advanced methods to find a divisor util fully factored
-    for (p, exp) in factorize128_advanced(&[(residual, 1usize)]).into_iter() {
-        *result.entry(p).or_insert(0) += exp;
+    // the cofactor is the target itself if no small factor is found
+    let method = match result.is_empty() {
+        true => FactorMethod::Prime,
+        false => FactorMethod::TrialDivision,
+    };
+    for (p, exp, m) in factorize128_advanced(&[(residual, 1, method)]).into_iter() {
+        result.entry(p).or_insert((0, m)).0 += exp;
     }
     result
 }
 
-pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, usize)> {
+pub(crate) fn factorize128_advanced(
+    cofactors: &[(u128, usize, FactorMethod)],
+) -> Vec<(u128, usize, FactorMethod)> {
     let (mut todo128, mut todo64) = (Vec::new(), Vec::new()); // cofactors to be processed
-    let mut factored: Vec<(u128, usize)> = Vec::new(); // prime factor, exponent
-    for &(co, e) in cofactors.iter() {
+    let mut factored: Vec<(u128, usize, FactorMethod)> = Vec::new(); // prime factor, exponent, method
+    for &(co, e, m) in cofactors.iter() {
         if let Ok(co64) = u64::try_from(co) {
-            todo64.push((co64, e));
+            todo64.push((co64, e, m));
         } else {
-            todo128.push((co, e));
+            todo128.push((co, e, m));
         };
     }
 
-    while let Some((target, exp)) = todo128.pop() {
+    while let Some((target, exp, method)) = todo128.pop() {
         if is_prime(&SmallMint::from(target), Some(PrimalityTestConfig::bpsw())).probably() {
-            factored.push((target, exp));
+            factored.push((target, exp, method));
             continue;
         }
 
@@ -402,17 +525,17 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
         // the smallest prime that haven't been checked is 8167^10 > 2^128
         if let Some(d) = target.sqrt_exact() {
             if let Ok(d64) = u64::try_from(d) {
-                todo64.push((d64, exp * 2));
+                todo64.push((d64, exp * 2, FactorMethod::PerfectPower));
             } else {
-                todo128.push((d, exp * 2));
+                todo128.push((d, exp * 2, FactorMethod::PerfectPower));
             }
             continue;
         }
         if let Some(d) = target.cbrt_exact() {
             if let Ok(d64) = u64::try_from(d) {
-                todo64.push((d64, exp * 3));
+                todo64.push((d64, exp * 3, FactorMethod::PerfectPower));
             } else {
-                todo128.push((d, exp * 3));
+                todo128.push((d, exp * 3, FactorMethod::PerfectPower));
             }
             continue;
         }
@@ -422,7 +545,7 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
         let mut i = 0usize;
         let mut max_iter_ratio = 1;
 
-        let divisor = loop {
+        let (divisor, method) = loop {
             // try various factorization method iteratively, sort by time per iteration
             const NMETHODS: usize = 3;
             match i % NMETHODS {
@@ -437,7 +560,7 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
                         offset.into(),
                         max_iter,
                     ) {
-                        break p.value();
+                        break (p.value(), FactorMethod::PollardRho);
                     }
                 }
                 1 => {
@@ -445,7 +568,7 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
                     let mul_target = target.checked_mul(480).unwrap_or(target);
                     let max_iter = max_iter_ratio << (mul_target.bits() / 6); // unoptimized heuristic
                     if let (Some(p), _) = one_line(&target, mul_target, max_iter) {
-                        break p;
+                        break (p, FactorMethod::OneLine);
                     }
                 }
                 2 => {
@@ -453,6 +576,15 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
                     let mut d = None;
                     for &k in SQUFOF_MULTIPLIERS.iter() {
                         if let Some(mul_target) = target.checked_mul(k as u128) {
+                            // SQUFOF can't proceed on a square, extract the factor directly
+                            if is_perfect_square(&mul_target) {
+                                let g = num_integer::gcd(target, mul_target.sqrt());
+                                if g > 1 && g < target {
+                                    d = Some(g);
+                                    break;
+                                }
+                                continue;
+                            }
                             let max_iter = max_iter_ratio * 2 * mul_target.sqrt().sqrt() as usize;
                             if let (Some(p), _) = squfof(&target, mul_target, max_iter) {
                                 d = Some(p);
@@ -461,7 +593,7 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
                         }
                     }
                     if let Some(p) = d {
-                        break p;
+                        break (p, FactorMethod::Squfof);
                     }
                 }
                 _ => unreachable!(),
@@ -475,23 +607,23 @@ pub(crate) fn factorize128_advanced(cofactors: &[(u128, usize)]) -> Vec<(u128, u
         };
 
         if let Ok(d64) = u64::try_from(divisor) {
-            todo64.push((d64, exp));
+            todo64.push((d64, exp, method));
         } else {
-            todo128.push((divisor, exp));
+            todo128.push((divisor, exp, method));
         }
         let co = target / divisor;
         if let Ok(d64) = u64::try_from(co) {
-            todo64.push((d64, exp));
+            todo64.push((d64, exp, method));
         } else {
-            todo128.push((co, exp));
+            todo128.push((co, exp, method));
         }
     }
 
     // forward 64 bit cofactors
     factored.extend(
-        factorize64_advanced(&todo64)
+        factorize64_advanced(&todo64, &mut rand::thread_rng())
             .into_iter()
-            .map(|(p, exp)| (p as u128, exp)),
+            .map(|(p, exp, m)| (p as u128, exp, m)),
     );
     factored
 }
@@ -506,6 +638,47 @@ where
     NaiveBuffer::new().is_prime(target, config)
 }
 
+/// Primality test that also returns the name of the decision path
+///
+/// This function re-exports [PrimeBufferExt::is_prime_explain()][crate::buffer::PrimeBufferExt::is_prime_explain()] with a new [NaiveBuffer] instance
+pub fn is_prime_explain<T: PrimalityBase>(
+    target: &T,
+    config: Option<PrimalityTestConfig>,
+) -> (Primality, &'static str)
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    NaiveBuffer::new().is_prime_explain(target, config)
+}
+
+/// Primality test on an integer given as a decimal string, which can be arbitrarily large.
+///
+/// The string is parsed into a [BigUint] (surrounding whitespaces are ignored) and tested with
+/// [is_prime()]. An error is returned if the string is not a valid non-negative decimal integer.
+#[cfg(feature = "num-bigint")]
+pub fn is_prime_str(
+    s: &str,
+    config: Option<PrimalityTestConfig>,
+) -> Result<Primality, ParseBigIntError> {
+    let target: BigUint = s.trim().parse()?;
+    Ok(is_prime(&target, config))
+}
+
+/// Filter an iterator of integers, yielding only the (probable) primes
+///
+/// This function uses [PrimeBufferExt::is_prime()][crate::buffer::PrimeBufferExt::is_prime()] with the given `config`
+/// on each element, with a single [NaiveBuffer] instance shared by all the tests.
+pub fn prime_filter<T: PrimalityBase, I: Iterator<Item = T>>(
+    iter: I,
+    config: Option<PrimalityTestConfig>,
+) -> impl Iterator<Item = T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let pb = NaiveBuffer::new();
+    iter.filter(move |x| pb.is_prime(x, config).probably())
+}
+
 /// Faillible factorization
 ///
 /// This function re-exports [PrimeBufferExt::factors()][crate::buffer::PrimeBufferExt::factors()] with a new [NaiveBuffer] instance
@@ -519,6 +692,85 @@ where
     NaiveBuffer::new().factors(target, config)
 }
 
+/// Faillible factorization with a known divisor of the target
+///
+/// The `hint` is divided out first, then the hint and the cofactor are factorized separately
+/// with [PrimeBufferExt::factors()][crate::buffer::PrimeBufferExt::factors()]. This is helpful
+/// when a large factor is already known.
+///
+/// If the hint is not a nontrivial divisor of the target, it's rejected without factorizing
+/// anything and the error is `vec![target]`. Otherwise the error contains the cofactors that
+/// failed to be factorized, which are all proper divisors of the target, so an error equal to
+/// `vec![target]` always means that the hint was rejected.
+pub fn factors_with_hint<T: PrimalityBase>(
+    target: T,
+    hint: &T,
+    config: Option<FactorizationConfig>,
+) -> Result<BTreeMap<T, usize>, Vec<T>>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if hint.is_zero() || hint.is_one() || hint == &target || !(&target % hint).is_zero() {
+        return Err(vec![target]);
+    }
+
+    let pb = NaiveBuffer::new();
+    let cofactor = target / hint;
+    let (mut result, mut failed) = pb.factors(hint.clone(), config);
+    let (cofactor_result, cofactor_failed) = pb.factors(cofactor, config);
+    for (p, e) in cofactor_result {
+        *result.entry(p).or_insert(0) += e;
+    }
+    if let Some(f) = cofactor_failed {
+        failed.get_or_insert_with(Vec::new).extend(f);
+    }
+    match failed {
+        None => Ok(result),
+        Some(failed) => Err(failed),
+    }
+}
+
+/// Format the prime factorization of an integer as a human-readable string like `2^2 * 3 * 7`.
+///
+/// The primes are in ascending order and the exponents equal to one are omitted. The
+/// factorization is done by [factors()], and the cofactors that failed to be factorized are
+/// appended to the end (so the product of the terms is always n). Zero and one are
+/// formatted as `0` and `1`.
+pub fn factorization_string<T: PrimalityBase + std::fmt::Display>(n: &T) -> String
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if n.is_zero() || n.is_one() {
+        return n.to_string();
+    }
+
+    let (factors, cofactors) = factors(n.clone(), None);
+    let mut terms: Vec<String> = factors
+        .into_iter()
+        .map(|(p, e)| match e {
+            1 => p.to_string(),
+            _ => format!("{}^{}", p, e),
+        })
+        .collect();
+    if let Some(cofactors) = cofactors {
+        terms.extend(cofactors.iter().map(|c| c.to_string()));
+    }
+    terms.join(" * ")
+}
+
+/// Faillible factorization that also reports how each prime factor is found
+///
+/// This function re-exports [PrimeBufferExt::factors_annotated()][crate::buffer::PrimeBufferExt::factors_annotated()] with a new [NaiveBuffer] instance
+pub fn factors_annotated<T: PrimalityBase>(
+    target: T,
+    config: Option<FactorizationConfig>,
+) -> Result<BTreeMap<T, (usize, FactorMethod)>, Vec<T>>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    NaiveBuffer::new().factors_annotated(target, config)
+}
+
 /// Infaillible factorization
 ///
 /// This function re-exports [PrimeBufferExt::factorize()][crate::buffer::PrimeBufferExt::factorize()] with a new [NaiveBuffer] instance
@@ -529,32 +781,73 @@ where
     NaiveBuffer::new().factorize(target)
 }
 
+thread_local! {
+    // shared prime buffer for the standalone prime functions, see clear_prime_cache()
+    static PRIME_CACHE: RefCell<NaiveBuffer> = RefCell::new(NaiveBuffer::new());
+}
+
+/// Get the precomputed table of small primes used by the trial divisions in this crate.
+///
+/// The element type depends on the `big-table` feature: the table contains the first 1024
+/// primes as [u16] if the feature is enabled, otherwise the 54 primes below 256 as [u8].
+#[cfg(not(feature = "big-table"))]
+pub fn small_primes() -> &'static [u8] {
+    &SMALL_PRIMES
+}
+
+/// Get the precomputed table of small primes used by the trial divisions in this crate.
+///
+/// The element type depends on the `big-table` feature: the table contains the first 1024
+/// primes as [u16] if the feature is enabled, otherwise the 54 primes below 256 as [u8].
+#[cfg(feature = "big-table")]
+pub fn small_primes() -> &'static [u16] {
+    &SMALL_PRIMES
+}
+
+/// Get the number of primes in the table returned by [small_primes()].
+pub fn small_primes_count() -> usize {
+    SMALL_PRIMES.len()
+}
+
 /// Get a list of primes under a limit
 ///
 /// This function re-exports [NaiveBuffer::primes()] and collect result as a vector.
+/// The sieved primes are kept in a thread-local buffer, see [clear_prime_cache()].
 pub fn primes(limit: u64) -> Vec<u64> {
-    NaiveBuffer::new().into_primes(limit).collect()
+    PRIME_CACHE.with(|pb| pb.borrow_mut().primes(limit).cloned().collect())
 }
 
 /// Get the first n primes
 ///
 /// This function re-exports [NaiveBuffer::nprimes()] and collect result as a vector.
+/// The sieved primes are kept in a thread-local buffer, see [clear_prime_cache()].
 pub fn nprimes(count: usize) -> Vec<u64> {
-    NaiveBuffer::new().into_nprimes(count).collect()
+    PRIME_CACHE.with(|pb| pb.borrow_mut().nprimes(count).cloned().collect())
 }
 
 /// Calculate and return the prime π function
 ///
-/// This function re-exports [NaiveBuffer::prime_pi()]
+/// This function re-exports [NaiveBuffer::prime_pi()]. The sieved primes are kept in a
+/// thread-local buffer, see [clear_prime_cache()].
 pub fn prime_pi(limit: u64) -> u64 {
-    NaiveBuffer::new().prime_pi(limit)
+    PRIME_CACHE.with(|pb| pb.borrow_mut().prime_pi(limit))
 }
 
 /// Get the n-th prime (n counts from 1).
 ///
-/// This function re-exports [NaiveBuffer::nth_prime()]
+/// This function re-exports [NaiveBuffer::nth_prime()]. The sieved primes are kept in a
+/// thread-local buffer, see [clear_prime_cache()].
 pub fn nth_prime(n: u64) -> u64 {
-    NaiveBuffer::new().nth_prime(n)
+    PRIME_CACHE.with(|pb| pb.borrow_mut().nth_prime(n))
+}
+
+/// Release the memory of the thread-local prime buffer shared by [primes()], [nprimes()],
+/// [prime_pi()] and [nth_prime()].
+///
+/// The standalone functions reuse the primes sieved in previous calls on the same thread,
+/// so the buffer only grows. Call this function to reclaim the memory.
+pub fn clear_prime_cache() {
+    PRIME_CACHE.with(|pb| pb.borrow_mut().clear())
 }
 
 /// Calculate the primorial function
@@ -565,16 +858,39 @@ pub fn primorial<T: PrimalityBase + std::iter::Product>(n: usize) -> T {
         .product()
 }
 
+/// Calculate the primorial function with checked multiplications, returns [None] if the
+/// result overflows the range of `T`.
+pub fn primorial_checked<T: PrimalityBase + CheckedMul>(n: usize) -> Option<T> {
+    NaiveBuffer::new()
+        .into_nprimes(n)
+        .try_fold(T::one(), |acc, p| acc.checked_mul(&T::from_u64(p)?))
+}
+
 /// This function calculate the Möbius `μ(n)` function of the input integer `n`
 ///
 /// This function behaves like `moebius_factorized(factorize(target))`.
 /// If the input integer is very hard to factorize, it's better to use
 /// the [factors()] function to control how the factorization is done, and then call
-/// [moebius_factorized()].
+/// [moebius_factorized()]. See also [try_moebius()] for a non-blocking version.
 ///
 /// # Panics
 /// if the factorization failed on target.
 pub fn moebius<T: PrimalityBase>(target: &T) -> i8
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    // retry until the factorization succeeds, same as factorize()
+    loop {
+        if let Some(m) = try_moebius(target, None) {
+            break m;
+        }
+    }
+}
+
+/// Calculate the Möbius `μ(n)` function like [moebius()], but the factorization (if required)
+/// is done only once with [factors()] and the given config. [None] is returned if the
+/// factorization failed, instead of retrying until it succeeds.
+pub fn try_moebius<T: PrimalityBase>(target: &T, config: Option<FactorizationConfig>) -> Option<i8>
 where
     for<'r> &'r T: PrimalityRefBase<T>,
 {
@@ -583,9 +899,9 @@ where
         let two = T::one() + T::one();
         let four = &two + &two;
         if (target % four).is_zero() {
-            return 0;
+            return Some(0);
         } else {
-            return -moebius(&(target / &two));
+            return try_moebius(&(target / &two), config).map(|m| -m);
         }
     }
 
@@ -594,7 +910,7 @@ where
         let m = MOEBIUS_ODD[(v >> 6) as usize];
         let m = m & (3 << (v & 63));
         let m = m >> (v & 63);
-        return m as i8 - 1;
+        return Some(m as i8 - 1);
     }
 
     // short cut for common primes
@@ -605,11 +921,14 @@ where
         || (target % five_sq).is_zero()
         || (target % seven_sq).is_zero()
     {
-        return 0;
+        return Some(0);
     }
 
     // then try complete factorization
-    moebius_factorized(&factorize(target.clone()))
+    match factors(target.clone(), config) {
+        (f, None) => Some(moebius_factorized(&f)),
+        (_, Some(_)) => None,
+    }
 }
 
 /// This function calculate the Möbius `μ(n)` function given the factorization
@@ -626,13 +945,76 @@ pub fn moebius_factorized<T>(factors: &BTreeMap<T, usize>) -> i8 {
 
 /// Tests if the integer doesn't have any square number factor.
 ///
+/// The small prime factors are removed by trial division first, and the complete
+/// factorization is only required when the remaining cofactor can't be decided directly.
+/// See also [try_is_square_free()] for a non-blocking version.
+///
 /// # Panics
 /// if the factorization failed on target.
 pub fn is_square_free<T: PrimalityBase>(target: &T) -> bool
 where
     for<'r> &'r T: PrimalityRefBase<T>,
 {
-    moebius(target) != 0
+    // retry until the factorization succeeds, same as factorize()
+    loop {
+        if let Some(result) = try_is_square_free(target, None) {
+            break result;
+        }
+    }
+}
+
+/// Tests if the integer doesn't have any square number factor like [is_square_free()], but the
+/// factorization (if required) is done only once with [factors()] and the given config.
+/// [None] is returned if the factorization failed, instead of retrying until it succeeds.
+pub fn try_is_square_free<T: PrimalityBase>(
+    target: &T,
+    config: Option<FactorizationConfig>,
+) -> Option<bool>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if target.is_zero() {
+        return Some(false);
+    }
+
+    // remove small prime factors, bail out on the first square factor
+    let mut n = target.clone();
+    for &p in SMALL_PRIMES.iter() {
+        let p: T = match try_from_u64(p as u64) {
+            Some(p) => p,
+            None => return Some(true), // p exceeds the range of T, so n is one or a prime
+        };
+        if p > &n / &p {
+            // n is one or a prime
+            return Some(true);
+        }
+        if (&n % &p).is_zero() {
+            n = n / &p;
+            if (&n % &p).is_zero() {
+                return Some(false);
+            }
+        }
+    }
+
+    // now all prime factors of n are at least SMALL_PRIMES_NEXT
+    if is_prime(&n, None).probably() {
+        return Some(true);
+    }
+    if n.is_square() {
+        return Some(false);
+    }
+    // a composite cofactor under SMALL_PRIMES_NEXT^3 is a product of two distinct primes
+    let below_cube = match try_from_u64::<T>(SMALL_PRIMES_NEXT) {
+        Some(next) => &n / &next / &next < next,
+        None => true,
+    };
+    if below_cube {
+        return Some(true);
+    }
+    match factors(n, config) {
+        (f, None) => Some(moebius_factorized(&f) != 0),
+        (_, Some(_)) => None,
+    }
 }
 
 /// Returns the estimated bounds (low, high) of prime π function, such that
@@ -827,14 +1209,9 @@ where
     // first search in small primes
     if let Some(x) = target.to_u8() {
         return match SMALL_PRIMES.binary_search(&x) {
-            Ok(pos) => {
-                if pos + 1 == SMALL_PRIMES.len() {
-                    T::from_u64(SMALL_PRIMES_NEXT)
-                } else {
-                    T::from_u8(SMALL_PRIMES[pos + 1])
-                }
-            }
-            Err(pos) => T::from_u8(SMALL_PRIMES[pos]),
+            Ok(pos) if pos + 1 < SMALL_PRIMES.len() => try_from_u64(SMALL_PRIMES[pos + 1] as u64),
+            Err(pos) if pos < SMALL_PRIMES.len() => try_from_u64(SMALL_PRIMES[pos] as u64),
+            _ => try_from_u64(SMALL_PRIMES_NEXT),
         };
     }
 
@@ -957,6 +1334,61 @@ where
     }
 }
 
+/// Find the first prime number larger than `target`, calling `on_candidate` on every candidate
+/// before it's tested. The search is aborted (and [None] is returned) as soon as the callback
+/// returns false.
+///
+/// The candidates are the integers on the wheel (i.e. coprime to the small primes in the wheel),
+/// this is useful for observing or cancelling long searches for large primes.
+/// For targets covered by the small prime table, the callback is invoked once on the result.
+pub fn next_prime_with_progress<T: PrimalityBase + CheckedAdd, F: FnMut(&T) -> bool>(
+    target: &T,
+    config: Option<PrimalityTestConfig>,
+    mut on_candidate: F,
+) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let last_small = *SMALL_PRIMES.last().unwrap() as u64;
+    if target.to_u64().is_some_and(|x| x < last_small) {
+        let p = next_prime(target, config)?;
+        return if on_candidate(&p) { Some(p) } else { None };
+    }
+
+    // moving along the wheel
+    let wheel = WHEEL_SIZE as u64;
+    let mut i = match try_from_u64::<T>(wheel) {
+        Some(w) => (target % w).to_u64().unwrap(),
+        None => target.to_u64().unwrap() % wheel,
+    };
+    let mut t = target.clone();
+    loop {
+        let offset = WHEEL_NEXT[i as usize];
+        t = t.checked_add(&T::from_u8(offset).unwrap())?;
+        i = (i + offset as u64) % wheel;
+        if !on_candidate(&t) {
+            break None;
+        }
+        if is_prime(&t, config).probably() {
+            break Some(t);
+        }
+    }
+}
+
+/// Find the first prime number larger than `target` with [next_prime()], and also return the
+/// gap to it, i.e. `p - target`. If the result causes an overflow, then [None] will be returned.
+pub fn next_prime_with_gap<T: PrimalityBase + CheckedAdd>(
+    target: &T,
+    config: Option<PrimalityTestConfig>,
+) -> Option<(T, u64)>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let p = next_prime(target, config)?;
+    let gap = (&p - target).to_u64()?;
+    Some((p, gap))
+}
+
 /// Estimate the value of prime π() function by averaging the estimated bounds.
 #[cfg(not(feature = "big-table"))]
 pub fn prime_pi_est<T: Num + ToPrimitive + FromPrimitive>(target: &T) -> T {
@@ -1025,343 +1457,5089 @@ where
     return Some(lo);
 }
 
-// TODO: More functions
-// REF: http://www.numbertheory.org/gnubc/bc_programs.html
-// REF: https://github.com/TilmanNeumann/java-math-library
-// - is_smooth: checks if the smoothness bound is at least b
-// - euler_phi: Euler's totient function
-// - jordan_tot: Jordan's totient function
-// Others include Louiville function, Mangoldt function, Dedekind psi function, Dickman rho function, etc..
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rand::{prelude::SliceRandom, random};
-    use std::iter::FromIterator;
-
-    #[test]
-    fn is_prime64_test() {
-        // test small primes
-        for x in 2..100 {
-            assert_eq!(SMALL_PRIMES.contains(&x), is_prime64(x as u64));
-        }
-        assert!(is_prime64(677));
-        
-        // from PR #7
-        assert!(!is_prime64(9773));
-        assert!(!is_prime64(13357));
-        assert!(!is_prime64(18769));
-
-        // some large primes
-        assert!(is_prime64(6469693333));
-        assert!(is_prime64(13756265695458089029));
-        assert!(is_prime64(13496181268022124907));
-        assert!(is_prime64(10953742525620032441));
-        assert!(is_prime64(17908251027575790097));
-
-        // primes from examples in Bradley Berg's hash method
-        assert!(is_prime64(480194653));
-        assert!(!is_prime64(20074069));
-        assert!(is_prime64(8718775377449));
-        assert!(is_prime64(3315293452192821991));
-        assert!(!is_prime64(8651776913431));
-        assert!(!is_prime64(1152965996591997761));
-
-        // false positives reported by JASory (#4)
-        assert!(!is_prime64(600437059821397));
-        assert!(!is_prime64(3866032210719337));
-        assert!(!is_prime64(4100599722623587));
+/// Test if the target is a (decimal) truncatable prime, i.e. it remains prime as the digits
+/// are successively removed from the left (if `left` is true) and/or from the right (if `right` is
+/// true). If both are false, this function is equivalent to [is_prime64].
+///
+/// Numbers containing the digit zero are not considered left-truncatable. The single digit
+/// primes 2, 3, 5, 7 are trivially truncatable in both directions.
+pub fn is_truncatable_prime(n: u64, left: bool, right: bool) -> bool {
+    if !is_prime64(n) {
+        return false;
+    }
 
-        // ensure no factor for 100 random primes
-        let mut rng = rand::thread_rng();
-        for _ in 0..100 {
-            let x = random();
-            if !is_prime64(x) {
-                continue;
+    if left {
+        let mut modulo = 10;
+        while modulo <= n {
+            let t = n % modulo;
+            if t < modulo / 10 || !is_prime64(t) {
+                // leading zero is not allowed
+                return false;
             }
-            assert_ne!(x % (*SMALL_PRIMES.choose(&mut rng).unwrap() as u64), 0);
+            modulo = match modulo.checked_mul(10) {
+                Some(m) => m,
+                None => break,
+            };
         }
+    }
 
-        // create random composites
-        for _ in 0..100 {
-            let x = random::<u32>() as u64;
-            let y = random::<u32>() as u64;
-            assert!(!is_prime64(x * y));
+    if right {
+        let mut t = n / 10;
+        while t > 0 {
+            if !is_prime64(t) {
+                return false;
+            }
+            t /= 10;
         }
     }
 
-    #[test]
-    fn factorize64_test() {
-        // some simple cases
-        let fac4095 = BTreeMap::from_iter([(3, 2), (5, 1), (7, 1), (13, 1)]);
+    true
+}
+
+/// Construct the repunit number `R_n = (10^n - 1) / 9`, i.e. the number consisting of `n` ones in decimal.
+#[cfg(feature = "num-bigint")]
+pub fn repunit(n: u32) -> BigUint {
+    (BigUint::from(10u8).pow(n) - 1u8) / 9u8
+}
+
+/// Test if the repunit number `R_n` is a prime. It will use the
+/// [strict primality test configuration][PrimalityTestConfig::strict()].
+///
+/// A repunit can be a prime only if `n` is a prime, so composite `n` is rejected without
+/// constructing `R_n`.
+#[cfg(feature = "num-bigint")]
+pub fn is_repunit_prime(n: u32) -> Primality {
+    if !is_prime64(n as u64) {
+        return Primality::No;
+    }
+    is_prime(&repunit(n), Some(PrimalityTestConfig::strict()))
+}
+
+/// Test if `N = k * 2^n - 1` is a prime with the Lucas-Lehmer-Riesel test.
+///
+/// The factors of two in k are moved into the exponent first. The test is deterministic
+/// if `k < 2^n` (and n >= 2) after that, otherwise N is tested by [is_prime()] instead.
+/// The starting value of the iteration is `V_k(P, 1) mod N`, where P is the smallest integer
+/// such that `(P-2|N) = 1` and `(P+2|N) = -1` (Rödseth's method), and N is a prime if and only
+/// if the (n-2)-th iterate of `u -> u^2 - 2` is zero. With `k = 1` this is the Lucas-Lehmer
+/// test for Mersenne numbers.
+///
+/// [None] is returned if N doesn't fit in the integer type T.
+///
+/// # Panics
+/// if k is zero.
+pub fn is_riesel_prime<T: PrimalityBase + CheckedMul>(k: &T, n: u32) -> Option<Primality>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!k.is_zero(), "k should be positive");
+    let shift = k.trailing_zeros();
+    let k = k >> shift;
+    let n = n.checked_add(shift as u32)?;
+    let power = num_traits::checked_pow(T::from_u8(2).unwrap(), n as usize)?;
+    let target = k.checked_mul(&power)? - T::one();
+    if n < 2 || k >= power {
+        return Some(is_prime(&target, None));
+    }
+    if target.is_square() {
+        return Some(Primality::No);
+    }
+
+    // select the parameter P, a zero Jacobi symbol indicates a common factor
+    let mut p = 3usize;
+    loop {
+        let (lo, hi) = (T::from_usize(p - 2).unwrap(), T::from_usize(p + 2).unwrap());
+        match (lo.jacobi(&target), hi.jacobi(&target)) {
+            (1, -1) => break,
+            (0, _) | (_, 0) if hi >= target => return Some(is_prime(&target, None)),
+            (0, _) | (_, 0) => return Some(Primality::No),
+            _ => p += 1,
+        }
+    }
+
+    let two = T::from_u8(2).unwrap();
+    let (_, mut u) = LucasUtils::lucasm(p, 1, target.clone(), k);
+    for _ in 0..n - 2 {
+        u = u.sqm(&target).subm(&two, &target);
+    }
+    Some(match u.is_zero() {
+        true => Primality::Yes,
+        false => Primality::No,
+    })
+}
+
+/// Calculate the integer logarithm `floor(log_base(n))` without floating point arithmetics,
+/// so the result is exact even for very large integers.
+///
+/// # Panics
+/// if `n` is zero or `base` is less than 2
+pub fn ilog<T: PrimalityBase>(n: &T, base: &T) -> u32
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "logarithm of zero is undefined");
+    assert!(
+        base > &T::one(),
+        "base of the logarithm should be larger than 1"
+    );
+
+    // collect base^(2^i) that are not larger than n
+    let mut powers = vec![];
+    let mut p = base.clone();
+    while &p <= n {
+        // check p^2 <= n by division to prevent overflow
+        let next = if p <= n / &p { Some(&p * &p) } else { None };
+        powers.push(p);
+        match next {
+            Some(v) => p = v,
+            None => break,
+        }
+    }
+
+    // binary search on the exponent
+    let mut result = 0;
+    let mut acc = T::one();
+    for (i, p) in powers.iter().enumerate().rev() {
+        if acc <= n / p {
+            acc = acc * p;
+            result += 1 << i;
+        }
+    }
+    result
+}
+
+/// Find the left and right neighbors of the fraction `p/q` in the Farey sequence of order `n`,
+/// returned as `((a, b), (c, d))` such that `a/b < p/q < c/d` are adjacent in the sequence.
+///
+/// The neighbors are found using the property `pb - aq = cq - pd = 1` of adjacent Farey fractions,
+/// so the denominators are determined by the modular inverse of `p` modulo `q`.
+///
+/// # Panics
+/// if `p/q` is not a reduced fraction in the open interval (0, 1) or `q > n`
+pub fn farey_neighbors(n: u64, p: u64, q: u64) -> ((u64, u64), (u64, u64)) {
+    assert!(0 < p && p < q && q <= n, "p/q should be a fraction in F_n");
+    let inv = p.invm(&q).expect("p/q should be a reduced fraction in F_n");
+
+    // the denominators are the largest ones under n in the residue class
+    let b = inv + (n - inv) / q * q;
+    let d = (q - inv) + (n - (q - inv)) / q * q;
+    let a = (p as u128 * b as u128 - 1) / q as u128;
+    let c = (p as u128 * d as u128 + 1) / q as u128;
+    ((a as u64, b), (c as u64, d))
+}
+
+/// Generate the Farey sequence of order `n`, i.e. all reduced fractions `p/q` in `[0, 1]`
+/// with `q <= n`, in ascending order. The fractions are returned as `(p, q)` pairs.
+///
+/// The terms are generated by the next-term recurrence of adjacent Farey fractions, so no
+/// gcd is required. The length of the sequence is `1 + totient_sum(n)`, and the result is
+/// empty if `n` is zero.
+pub fn farey_sequence(n: u64) -> Vec<(u64, u64)> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut seq = vec![(0, 1)];
+    let (mut a, mut b, mut c, mut d) = (0u64, 1u64, 1u64, n);
+    while c <= n {
+        seq.push((c, d));
+        let k = (n + b) / d;
+        let (e, f) = (k * c - a, k * d - b);
+        a = c;
+        b = d;
+        c = e;
+        d = f;
+    }
+    seq
+}
+
+/// Precompute the Jacobi symbol `(a|n)` for all `a` in `[0, n)` with a fixed odd modulus `n`.
+///
+/// The table is filled using the complete multiplicativity of the Jacobi symbol, so only the
+/// symbols of the primes below `n` are evaluated directly. The returned vector has length `n`,
+/// so the modulus is limited to fit in `u32` to prevent excessive memory usage.
+///
+/// # Panics
+/// if `n` is even or it doesn't fit in `u32`
+pub fn jacobi_table<T: PrimalityBase>(n: &T) -> Vec<i8>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let n = n
+        .to_u32()
+        .expect("the modulus is too large for a Jacobi symbol table");
+    assert!(
+        n % 2 == 1,
+        "the Jacobi symbol is only defined for odd modulus"
+    );
+
+    let mut table = vec![1i8; n as usize];
+    if n == 1 {
+        return table;
+    }
+    table[0] = 0;
+
+    for p in NaiveBuffer::new().into_primes(n as u64 - 1) {
+        let p = p as u32;
+        match p.jacobi(&n) {
+            1 => {}
+            0 => {
+                for m in (p..n).step_by(p as usize) {
+                    table[m as usize] = 0;
+                }
+            }
+            _ => {
+                // flip the sign once for every power of p in m
+                let mut pk = p as u64;
+                while pk < n as u64 {
+                    for m in (pk..n as u64).step_by(pk as usize) {
+                        table[m as usize] = -table[m as usize];
+                    }
+                    pk *= p as u64;
+                }
+            }
+        }
+    }
+    table
+}
+
+/// Count how many consecutive values of the quadratic polynomial `a*n^2 + b*n + c` are prime
+/// (in absolute value) for `n = 0, 1, 2, ...`, up to at most `max_n` values.
+///
+/// The famous Euler's polynomial `n^2 + n + 41` gives a run of 40 primes. The run also stops if
+/// the polynomial value overflows `u64`.
+pub fn poly_prime_run(a: i64, b: i64, c: i64, max_n: u64) -> u64 {
+    let value = |n: u64| -> Option<u64> {
+        let n = i128::from(n);
+        let v = i128::from(a)
+            .checked_mul(n.checked_mul(n)?)?
+            .checked_add(i128::from(b).checked_mul(n)?)?
+            .checked_add(i128::from(c))?;
+        u64::try_from(v.checked_abs()?).ok()
+    };
+
+    let mut count = 0;
+    while count < max_n {
+        match value(count) {
+            Some(v) if is_prime64(v) => count += 1,
+            _ => break,
+        }
+    }
+    count
+}
+
+/// Generate all primitive Pythagorean triples `(a, b, c)` with `a^2 + b^2 = c^2`, `a < b` and
+/// perimeter `a + b + c <= perimeter_limit`. The triples are sorted in ascending order.
+///
+/// The triples are generated with Euclid's formula `(m^2 - n^2, 2mn, m^2 + n^2)`, where `m > n`
+/// are coprime integers with opposite parity.
+pub fn primitive_pythagorean_triples(perimeter_limit: u64) -> Vec<(u64, u64, u64)> {
+    let mut triples = Vec::new();
+
+    // the perimeter is 2m(m+n) >= 2m(m+1)
+    let mut m = 2u64;
+    while 2 * m * (m + 1) <= perimeter_limit {
+        for n in (1 + m % 2..m).step_by(2) {
+            if 2 * m * (m + n) > perimeter_limit {
+                break;
+            }
+            if num_integer::gcd(m, n) != 1 {
+                continue;
+            }
+            let (a, b, c) = (m * m - n * n, 2 * m * n, m * m + n * n);
+            triples.push((a.min(b), a.max(b), c));
+        }
+        m += 1;
+    }
+    triples.sort_unstable();
+    triples
+}
+
+/// Calculate the Euler's totient function φ(n) of an u64 integer.
+pub fn euler_phi(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    factorize64(n)
+        .into_iter()
+        .map(|(p, e)| (p - 1) * p.pow(e as u32 - 1))
+        .product()
+}
+
+/// Returns true if the multiplicative group modulo n is cyclic (i.e. a primitive root exists),
+/// where `n` is given by its factorization. This is the case when n is 1, 2, 4, p^k or 2p^k.
+fn has_primitive_root(factors: &BTreeMap<u64, usize>) -> bool {
+    let twos = factors.get(&2).copied().unwrap_or(0);
+    let odd_count = factors.len() - (twos > 0) as usize;
+    match odd_count {
+        0 => twos <= 2,
+        1 => twos <= 1,
+        _ => false,
+    }
+}
+
+/// Returns true if g is a primitive root modulo n, given φ(n) and its distinct prime factors.
+fn is_primitive_root(g: u64, n: u64, phi: u64, phi_primes: &[u64]) -> bool {
+    num_integer::gcd(g, n) == 1 && phi_primes.iter().all(|&q| g.powm(phi / q, &n) != 1)
+}
+
+/// Find the smallest primitive root modulo n. Returns [None] if there's no primitive root
+/// (n is not 1, 2, 4, p^k or 2p^k). The primitive root modulo 1 is defined to be 0.
+pub fn primitive_root(n: u64) -> Option<u64> {
+    if n <= 2 {
+        return n.checked_sub(1);
+    }
+    let factors = factorize64(n);
+    if !has_primitive_root(&factors) {
+        return None;
+    }
+
+    let phi: u64 = factors
+        .into_iter()
+        .map(|(p, e)| (p - 1) * p.pow(e as u32 - 1))
+        .product();
+    let phi_primes: Vec<u64> = factorize64(phi).into_keys().collect();
+    (2..n).find(|&g| is_primitive_root(g, n, phi, &phi_primes))
+}
+
+/// Generate the table of smallest primitive roots modulo n for all `n <= limit`, where the
+/// entry is [None] if there's no primitive root modulo n (including n = 0). This is much
+/// faster than calling [primitive_root] repeatedly, since the factorizations of n and φ(n)
+/// are obtained from a shared smallest prime factor sieve.
+pub fn primitive_root_table(limit: u64) -> Vec<Option<u32>> {
+    let size = limit as usize + 1;
+
+    // sieve the smallest prime factors
+    let mut spf: Vec<u64> = (0..size as u64).collect();
+    let mut i = 2;
+    while i * i < size {
+        if spf[i] == i as u64 {
+            for j in (i * i..size).step_by(i) {
+                if spf[j] == j as u64 {
+                    spf[j] = i as u64;
+                }
+            }
+        }
+        i += 1;
+    }
+    let factorize = |mut x: u64| {
+        let mut factors = BTreeMap::new();
+        while x > 1 {
+            let p = spf[x as usize];
+            x /= p;
+            *factors.entry(p).or_insert(0) += 1;
+        }
+        factors
+    };
+
+    let mut table = vec![None; size];
+    for n in 1..size as u64 {
+        if n <= 2 {
+            table[n as usize] = Some(n as u32 - 1);
+            continue;
+        }
+        let factors = factorize(n);
+        if !has_primitive_root(&factors) {
+            continue;
+        }
+        let phi: u64 = factors
+            .into_iter()
+            .map(|(p, e)| (p - 1) * p.pow(e as u32 - 1))
+            .product();
+        let phi_primes: Vec<u64> = factorize(phi).into_keys().collect();
+        table[n as usize] = (2..n)
+            .find(|&g| is_primitive_root(g, n, phi, &phi_primes))
+            .map(|g| g as u32);
+    }
+    table
+}
+
+/// Find the smallest prime `p >= 2^min_bits` such that `2^transform_size_log2` divides `p - 1`,
+/// which is suitable as the modulus for a number-theoretic transform of size up to
+/// `2^transform_size_log2`. The prime is returned together with its smallest primitive root.
+///
+/// Returns [None] if there's no such prime in the u64 range.
+pub fn find_ntt_prime(min_bits: usize, transform_size_log2: u32) -> Option<(u64, u64)> {
+    if min_bits > 64 || transform_size_log2 >= 64 {
+        return None;
+    }
+    let step = 1u64 << transform_size_log2;
+    let lower = 1u128 << min_bits;
+
+    // the candidates are c * 2^k + 1 with c >= 1
+    let mut c = (lower - 1).div_ceil(step as u128).max(1);
+    loop {
+        let p = u64::try_from(c * step as u128 + 1).ok()?;
+        if is_prime64(p) {
+            return Some((p, primitive_root(p).unwrap()));
+        }
+        c += 1;
+    }
+}
+
+/// Find the multiplicative order of `base` modulo `modulus`, given a multiple of the order
+/// (typically the group order, e.g. φ(modulus) or p - 1 for a prime modulus) and its
+/// factorization. The result is unspecified if the `group_order` is not a multiple of the order.
+///
+/// This is useful when the group order and its factorization are known in advance, since
+/// [multiplicative_order] needs to factorize both the modulus and φ(modulus).
+pub fn order_dividing<T: PrimalityBase>(
+    base: &T,
+    modulus: &T,
+    group_order: &T,
+    group_order_factors: &BTreeMap<T, usize>,
+) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let mut order = group_order.clone();
+    for (p, &e) in group_order_factors {
+        for _ in 0..e {
+            let next = &order / p;
+            if !base.clone().powm(&next, modulus).is_one() {
+                break;
+            }
+            order = next;
+        }
+    }
+    order
+}
+
+/// Find the multiplicative order of `base` modulo `modulus`, i.e. the smallest positive
+/// integer k such that `base^k = 1 (mod modulus)`. Returns [None] if `base` and `modulus`
+/// are not coprime.
+pub fn multiplicative_order<T: PrimalityBase>(base: &T, modulus: &T) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if modulus.is_zero() || !base.gcd(modulus).is_one() {
+        return None;
+    }
+    if modulus.is_one() {
+        return Some(T::one());
+    }
+
+    let phi = factorize(modulus.clone())
+        .into_iter()
+        .map(|(p, e)| num_traits::pow(p.clone(), e - 1) * (p - T::one()))
+        .fold(T::one(), |acc, v| acc * v);
+    let phi_factors = factorize(phi.clone());
+    Some(order_dividing(base, modulus, &phi, &phi_factors))
+}
+
+/// Calculate the divisor function σ_k(n), i.e. the sum of the k-th powers of all the divisors
+/// of n. σ_0(n) is the number of divisors and σ_1(n) is the sum of divisors.
+///
+/// # Panics
+/// if target is zero.
+pub fn divisor_sigma<T: PrimalityBase>(target: &T, k: u32) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!target.is_zero(), "divisor function of zero is undefined");
+    let mut result = T::one();
+    for (p, e) in factorize(target.clone()) {
+        // sum of 1 + p^k + p^2k + ... + p^ek
+        let pk = num_traits::pow(p, k as usize);
+        let (mut term, mut sum) = (T::one(), T::one());
+        for _ in 0..e {
+            term = term * &pk;
+            sum = sum + &term;
+        }
+        result = result * sum;
+    }
+    result
+}
+
+/// Calculate the sum of the distinct prime factors of n (sopf), e.g. sopf(12) = 2 + 3.
+/// By convention sopf(1) = 0.
+///
+/// # Panics
+/// if target is zero.
+pub fn sopf<T: PrimalityBase>(target: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!target.is_zero(), "sopf of zero is undefined");
+    factorize(target.clone())
+        .into_iter()
+        .fold(T::zero(), |sum, (p, _)| sum + p)
+}
+
+/// Calculate the sum of the prime factors of n with repetition (sopfr, also known as the
+/// integer logarithm), e.g. sopfr(12) = 2 + 2 + 3. By convention sopfr(1) = 0.
+///
+/// # Panics
+/// if target is zero.
+pub fn sopfr<T: PrimalityBase>(target: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!target.is_zero(), "sopfr of zero is undefined");
+    factorize(target.clone())
+        .into_iter()
+        .fold(T::zero(), |sum, (p, e)| sum + p * T::from_usize(e).unwrap())
+}
+
+/// Test if `(n, n+1)` is a Ruth-Aaron pair, i.e. [sopfr()] of the two consecutive integers
+/// are equal. For example, 714 = 2 * 3 * 7 * 17 and 715 = 5 * 11 * 13 both sum to 29.
+pub fn is_ruth_aaron_pair(n: u64) -> bool {
+    match n.checked_add(1) {
+        Some(m) if n > 0 => sopfr(&n) == sopfr(&m),
+        _ => false,
+    }
+}
+
+/// Find all n such that `(n, n+1)` is a Ruth-Aaron pair (see [is_ruth_aaron_pair()]) with
+/// `n + 1 <= limit`. The sums of the prime factors are computed once for each integer.
+pub fn ruth_aaron_pairs(limit: u64) -> Vec<u64> {
+    let mut pairs = Vec::new();
+    let mut last = 0; // sopfr(1)
+    for m in 2..=limit {
+        let current = sopfr(&m);
+        if current == last {
+            pairs.push(m - 1);
+        }
+        last = current;
+    }
+    pairs
+}
+
+/// Estimate the difficulty of factorizing an integer without doing the full factorization.
+///
+/// This function runs trial division with the small primes and a primality test on the
+/// cofactor, so that the caller can decide whether to attempt the factorization. Integers
+/// under 2^64 are always considered [FactorDifficulty::Easy] (or prime), as [factorize64] is fast.
+pub fn factorization_hint<T: PrimalityBase>(target: &T) -> FactorDifficulty
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if is_prime(target, None).probably() {
+        return FactorDifficulty::Prime;
+    }
+    if target.to_u64().is_some() {
+        return FactorDifficulty::Easy;
+    }
+
+    // remove small prime factors
+    let mut n = target.clone();
+    for &p in SMALL_PRIMES.iter() {
+        let p = T::from_u64(p as u64).unwrap();
+        while (&n % &p).is_zero() {
+            n = n / &p;
+        }
+    }
+    if n.to_u64().is_some() || is_prime(&n, None).probably() || n.is_square() {
+        FactorDifficulty::Easy
+    } else {
+        FactorDifficulty::Hard
+    }
+}
+
+/// Calculate the prime signature of an integer, i.e. the sorted exponents of its prime
+/// factorization. For example, the prime signature of 12 = 2^2 * 3 is `[1, 2]`.
+///
+/// # Panics
+/// if target is zero.
+pub fn prime_signature<T: PrimalityBase>(target: &T) -> Vec<usize>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!target.is_zero(), "prime signature of zero is undefined");
+    let mut signature: Vec<usize> = factorize(target.clone()).into_values().collect();
+    signature.sort_unstable();
+    signature
+}
+
+/// Test if an integer is B-powersmooth, i.e. every prime power `p^k` exactly dividing it
+/// satisfies `p^k <= bound`. Zero is not considered powersmooth.
+pub fn is_powersmooth<T: PrimalityBase>(target: &T, bound: &T) -> bool
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if target.is_zero() {
+        return false;
+    }
+    factorize(target.clone())
+        .into_iter()
+        .all(|(p, e)| &num_traits::pow(p, e) <= bound)
+}
+
+/// Calculate the abundancy index σ(n)/n, returned as a reduced fraction (numerator, denominator).
+///
+/// # Panics
+/// if target is zero.
+pub fn abundancy_index<T: PrimalityBase>(n: &T) -> (T, T)
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let sigma = divisor_sigma(n, 1);
+    let g = sigma.gcd(n);
+    (sigma / &g, n / &g)
+}
+
+/// Test if two positive integers are friendly, i.e. they share the same abundancy index.
+pub fn are_friendly(a: u64, b: u64) -> bool {
+    abundancy_index(&a) == abundancy_index(&b)
+}
+
+/// Calculate the greatest common divisor of a list of integers. The result is zero if the
+/// list is empty.
+pub fn gcd_many<T: PrimalityBase>(values: &[T]) -> T {
+    let mut result = T::zero();
+    for v in values {
+        result = result.gcd(v);
+        if result.is_one() {
+            break;
+        }
+    }
+    result
+}
+
+/// Calculate the least common multiple of a list of integers. The result is one if the list
+/// is empty, and it's [None] if the result overflows the range of `T`.
+pub fn lcm_many<T: PrimalityBase + CheckedMul>(values: &[T]) -> Option<T> {
+    let mut result = T::one();
+    for v in values {
+        if v.is_zero() {
+            return Some(T::zero());
+        }
+        let g = result.gcd(v);
+        result = (result / g).checked_mul(v)?;
+    }
+    Some(result)
+}
+
+/// Calculate the structure of the multiplicative group (Z/nZ)*, represented by its invariant
+/// factors `[d1, d2, ..., dk]` where `d1 | d2 | ... | dk` and the group is isomorphic to
+/// `C_d1 × C_d2 × ... × C_dk`. The result is empty if the group is trivial (n = 1 or 2).
+///
+/// This function behaves like `unit_group_structure_factorized(factorize(n))`.
+///
+/// # Panics
+/// if n is zero.
+pub fn unit_group_structure<T: PrimalityBase>(n: &T) -> Vec<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "the unit group of Z/0Z is infinite");
+    unit_group_structure_factorized(&factorize(n.clone()))
+}
+
+/// Calculate the structure of the multiplicative group (Z/nZ)* given the factorization
+/// result of `n`, see [unit_group_structure()].
+pub fn unit_group_structure_factorized<T: PrimalityBase>(factors: &BTreeMap<T, usize>) -> Vec<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    // collect the exponents of the prime power cyclic components
+    let mut components: BTreeMap<T, Vec<usize>> = BTreeMap::new();
+    let two = T::from_u8(2).unwrap();
+    for (p, &k) in factors {
+        if p == &two {
+            // (Z/2^k)* is C2 × C_{2^(k-2)} for k >= 3
+            match k {
+                1 => {}
+                2 => components.entry(p.clone()).or_default().push(1),
+                _ => components.entry(p.clone()).or_default().extend([1, k - 2]),
+            }
+            continue;
+        }
+
+        // (Z/p^k)* is cyclic of order p^(k-1) * (p-1) for odd p
+        for (q, e) in factorize(p - T::one()) {
+            components.entry(q).or_default().push(e);
+        }
+        if k > 1 {
+            components.entry(p.clone()).or_default().push(k - 1);
+        }
+    }
+
+    // combine the components into invariant factors, from the largest to the smallest
+    let count = components.values().map(|v| v.len()).max().unwrap_or(0);
+    for exps in components.values_mut() {
+        exps.sort_unstable_by(|a, b| b.cmp(a));
+    }
+    let mut factors: Vec<T> = (0..count)
+        .map(|i| {
+            components
+                .iter()
+                .filter_map(|(p, exps)| exps.get(i).map(|&e| num_traits::pow(p.clone(), e)))
+                .fold(T::one(), |acc, v| acc * v)
+        })
+        .collect();
+    factors.reverse();
+    factors
+}
+
+/// Calculate the order of the multiplicative group (Z/nZ)*, which equals to the Euler's
+/// totient φ(n) (see [euler_phi] for u64 integers).
+///
+/// This function behaves like `unit_group_order_factorized(factorize(n))`.
+///
+/// # Panics
+/// if n is zero.
+pub fn unit_group_order<T: PrimalityBase>(n: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "the unit group of Z/0Z is infinite");
+    unit_group_order_factorized(&factorize(n.clone()))
+}
+
+/// Calculate the order of the multiplicative group (Z/nZ)* given the factorization result of
+/// `n`, i.e. the product of `p^(e-1) * (p-1)`. The same factorization can be shared with
+/// [unit_group_structure_factorized()].
+pub fn unit_group_order_factorized<T: PrimalityBase>(factors: &BTreeMap<T, usize>) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    factors.iter().fold(T::one(), |acc, (p, &e)| {
+        acc * num_traits::pow(p.clone(), e - 1) * (p - T::one())
+    })
+}
+
+/// Calculate the number of iterations of the Euler's totient function φ needed to reach 1
+/// starting from n (the class of n under iterated totients), see OEIS A003434.
+///
+/// The chain length of 1 is zero, and the chain length of 2^k is k.
+///
+/// # Panics
+/// if n is zero.
+pub fn totient_chain_length<T: PrimalityBase>(n: &T) -> usize
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "the totient chain of zero never reaches one");
+    let mut value = n.clone();
+    let mut length = 0;
+    while !value.is_one() {
+        value = unit_group_order(&value);
+        length += 1;
+    }
+    length
+}
+
+/// Find an r-th root of `a` modulo prime `p`, where r is a prime factor of p - 1 and `a` is
+/// a nonzero r-th power residue. This is the generalization of the Tonelli-Shanks algorithm.
+fn rth_root_mod_prime<T: PrimalityBase>(a: &T, r: &T, p: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    // p - 1 = r^t * s, where s is coprime to r
+    let m = p - T::one();
+    let (mut s, mut t) = (m.clone(), 0usize);
+    while (&s % r).is_zero() {
+        s = s / r;
+        t += 1;
+    }
+
+    // initial guess x = a^α with rα = 1 (mod s), the error x^r / a is in the Sylow r-subgroup
+    let alpha = if s.is_one() {
+        T::zero()
+    } else {
+        (r % &s).invm(&s).unwrap()
+    };
+    let x = a.clone().powm(&alpha, p);
+    let err = x.clone().powm(r, p).mulm(&a.clone().invm(p).unwrap(), p);
+    if err.is_one() {
+        return x;
+    }
+
+    // find a generator z of the Sylow r-subgroup from an r-th power non-residue
+    let mr = &m / r;
+    let mut c = T::from_u8(2).unwrap();
+    while c.clone().powm(&mr, p).is_one() {
+        c = c + T::one();
+    }
+    let z = c.powm(&s, p);
+    let zinv = z.clone().invm(p).unwrap();
+
+    // find the discrete logarithm j of the error in base z digit by digit, j is a multiple of r
+    let gamma = z.powm(&num_traits::pow(r.clone(), t - 1), p);
+    let (mut j, mut rk) = (T::zero(), T::one());
+    for i in 0..t {
+        let h = zinv.clone().powm(&j, p).mulm(&err, p);
+        let h = h.powm(&num_traits::pow(r.clone(), t - 1 - i), p);
+        let (mut delta, mut g) = (T::zero(), T::one());
+        while g != h {
+            g = g.mulm(&gamma, p);
+            delta = delta + T::one();
+        }
+        j = j + delta * &rk;
+        rk = rk * r;
+    }
+
+    // correct the guess by z^(-j/r)
+    x.mulm(&zinv.powm(&(j / r), p), p)
+}
+
+/// Find all the solutions of `x^n = a (mod p)` where p is a prime. The result is sorted and
+/// it's empty if `a` is not an n-th power residue modulo p.
+///
+/// The roots are found with the Adleman-Manders-Miller algorithm (generalized Tonelli-Shanks),
+/// which takes an r-th root for each prime factor r of gcd(n, p - 1). The running time is linear
+/// in r when r^2 divides p - 1, so it can be slow if p - 1 has a large repeated prime factor
+/// that also divides n.
+///
+/// # Panics
+/// if `n` is zero. The result is unspecified if `p` is not a prime.
+pub fn root_mod_prime<T: PrimalityBase>(a: &T, n: &T, p: &T) -> Vec<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "the exponent should be positive");
+    let a = a % p;
+    if a.is_zero() {
+        return vec![T::zero()];
+    }
+
+    // check if a is an n-th power residue
+    let m = p - T::one();
+    let d = n.gcd(&m);
+    let md = &m / &d;
+    if !a.clone().powm(&md, p).is_one() {
+        return Vec::new();
+    }
+
+    // solve y^d = a by taking r-th roots successively
+    let d_factors = factorize(d.clone());
+    let mut y = a;
+    for (r, &e) in &d_factors {
+        for _ in 0..e {
+            y = rth_root_mod_prime(&y, r, p);
+        }
+    }
+
+    // then x = y^w with w = (n/d)^-1 (mod (p-1)/d) is a solution
+    let w = if md.is_one() {
+        T::zero()
+    } else {
+        ((n / &d) % &md).invm(&md).unwrap()
+    };
+    let x = y.powm(&w, p);
+
+    // find a primitive d-th root of unity to generate all the solutions
+    let mut c = T::one();
+    let zeta = loop {
+        c = c + T::one();
+        let zeta = c.clone().powm(&md, p);
+        if d_factors
+            .keys()
+            .all(|q| !zeta.clone().powm(&(&d / q), p).is_one())
+        {
+            break zeta;
+        }
+    };
+    let mut roots = vec![x];
+    for _ in 1..d.to_usize().expect("too many roots") {
+        let next = roots.last().unwrap().clone().mulm(&zeta, p);
+        roots.push(next);
+    }
+    roots.sort();
+    roots
+}
+
+/// A Gaussian integer `re + im*i`, i.e. an element of `Z[i]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaussianInt {
+    pub re: i64,
+    pub im: i64,
+}
+
+impl GaussianInt {
+    #[inline]
+    pub fn new(re: i64, im: i64) -> Self {
+        GaussianInt { re, im }
+    }
+
+    /// Calculate the norm `re^2 + im^2` of the Gaussian integer
+    #[inline]
+    pub fn norm(&self) -> u128 {
+        let re = self.re.unsigned_abs() as u128;
+        let im = self.im.unsigned_abs() as u128;
+        re * re + im * im
+    }
+
+    /// Test if the Gaussian integer is a Gaussian prime.
+    ///
+    /// `a + bi` is a Gaussian prime iff its norm is a rational prime, or it's an associate of
+    /// a rational prime `p` with `p ≡ 3 (mod 4)`. The [strict primality test configuration][PrimalityTestConfig::strict()]
+    /// will be used if the norm is larger than 2^64.
+    pub fn is_prime_gaussian(&self) -> bool {
+        let m = if self.re == 0 {
+            self.im.unsigned_abs()
+        } else if self.im == 0 {
+            self.re.unsigned_abs()
+        } else {
+            return is_prime(&self.norm(), Some(PrimalityTestConfig::strict())).probably();
+        };
+        m % 4 == 3 && is_prime64(m)
+    }
+}
+
+/// Find the first `count` primes in the arithmetic progression `first, first + diff, first + 2*diff, ...`.
+///
+/// If `gcd(first, diff) > 1`, only the first term can be a prime, so the result contains at most
+/// one element. The result can also be shorter than `count` if the progression exceeds the u64 range.
+pub fn primes_in_ap(first: u64, diff: u64, count: usize) -> Vec<u64> {
+    if count == 0 {
+        return Vec::new();
+    }
+    if diff == 0 || num_integer::gcd(first, diff) > 1 {
+        let mut result = Vec::new();
+        if is_prime64(first) {
+            result.push(first);
+        }
+        return result;
+    }
+
+    let mut result = Vec::with_capacity(count);
+    let mut term = Some(first);
+    while let Some(t) = term {
+        if is_prime64(t) {
+            result.push(t);
+            if result.len() == count {
+                break;
+            }
+        }
+        term = t.checked_add(diff);
+    }
+    result
+}
+
+/// Calculate the length of the repeating block in the base-`base` expansion of 1/n.
+///
+/// The period equals the multiplicative order of `base` modulo the part of n coprime to `base`;
+/// factors shared with `base` only contribute to the non-repeating prefix. Returns 0 if the
+/// expansion terminates.
+///
+/// # Panics
+/// if n is zero or base is less than 2.
+pub fn decimal_period(n: u64, base: u64) -> u64 {
+    assert!(n > 0, "1/0 has no expansion");
+    assert!(base >= 2, "base must be at least 2");
+
+    // strip the factors shared with the base
+    let mut m = n;
+    loop {
+        let g = num_integer::gcd(m, base);
+        if g == 1 {
+            break;
+        }
+        m /= g;
+    }
+
+    if m == 1 {
+        0
+    } else {
+        multiplicative_order(&(base % m), &m).unwrap()
+    }
+}
+
+/// Build a small constant from the primitives by repeated additions of one
+fn small_constant<T: ModularPrimitives>(k: u8) -> T {
+    (0..k).fold(T::zero(), |acc, _| acc + T::one())
+}
+
+/// Calculate `base^exp mod m` using only the primitives from [ModularPrimitives].
+///
+/// # Panics
+/// if m is zero
+pub fn generic_powm<T: ModularPrimitives>(base: &T, exp: &T, m: &T) -> T {
+    assert!(!m.is_zero(), "modulus is zero");
+    let two: T = small_constant(2);
+
+    let mut result = T::one().div_rem(m).1;
+    let mut b = base.div_rem(m).1;
+    let mut e = exp.clone();
+    while !e.is_zero() {
+        let (q, r) = e.div_rem(&two);
+        if r.is_one() {
+            result = result.mulm(&b, m);
+        }
+        b = b.mulm(&b, m);
+        e = q;
+    }
+    result
+}
+
+/// Calculate the modular inverse of `a` modulo `m` using only the primitives from
+/// [ModularPrimitives]. Returns [None] if the inverse doesn't exist.
+///
+/// # Panics
+/// if m is zero
+pub fn generic_invm<T: ModularPrimitives>(a: &T, m: &T) -> Option<T> {
+    assert!(!m.is_zero(), "modulus is zero");
+
+    // extended Euclidean algorithm, with the coefficients kept reduced modulo m
+    let (mut r0, mut r1) = (m.clone(), a.div_rem(m).1);
+    let (mut t0, mut t1) = (T::zero(), T::one().div_rem(m).1);
+    while !r1.is_zero() {
+        let (q, r2) = r0.div_rem(&r1);
+        let t2 = t0.subm(&q.div_rem(m).1.mulm(&t1, m), m);
+        r0 = core::mem::replace(&mut r1, r2);
+        t0 = core::mem::replace(&mut t1, t2);
+    }
+
+    if r0.is_one() {
+        Some(t0)
+    } else {
+        None
+    }
+}
+
+/// Calculate the Jacobi symbol `(a/n)` using only the primitives from [ModularPrimitives].
+///
+/// # Panics
+/// if n is even
+pub fn generic_jacobi<T: ModularPrimitives>(a: &T, n: &T) -> i8 {
+    let (two, four, eight): (T, T, T) = (small_constant(2), small_constant(4), small_constant(8));
+    let (three, five): (T, T) = (small_constant(3), small_constant(5));
+    assert!(n.div_rem(&two).1.is_one(), "n must be odd");
+
+    let (mut a, mut n) = (a.div_rem(n).1, n.clone());
+    let mut sign = 1i8;
+    while !a.is_zero() {
+        loop {
+            let (q, r) = a.div_rem(&two);
+            if !r.is_zero() {
+                break;
+            }
+            a = q;
+            let n8 = n.div_rem(&eight).1;
+            if n8 == three || n8 == five {
+                sign = -sign;
+            }
+        }
+
+        core::mem::swap(&mut a, &mut n);
+        if a.div_rem(&four).1 == three && n.div_rem(&four).1 == three {
+            sign = -sign;
+        }
+        a = a.div_rem(&n).1;
+    }
+
+    if n.is_one() {
+        sign
+    } else {
+        0
+    }
+}
+
+/// Find a nontrivial factor of `target` with Shanks's square forms factorization (SQUFOF),
+/// trying each of the given multipliers in turn.
+///
+/// `max_iters` caps the number of iterations spent on each multiplier, 2*(k*n)^(1/4) is usually
+/// enough (see [squfof][crate::factor::squfof]). Multipliers that make `k * target` overflow are
+/// skipped. Good multipliers are listed in [SQUFOF_MULTIPLIERS][crate::factor::SQUFOF_MULTIPLIERS].
+///
+/// Perfect squares are handled directly. Returns [None] if no factor is found, which is always
+/// the case for primes.
+pub fn squfof_full<T: PrimalityBase + CheckedMul + std::fmt::Debug>(
+    target: &T,
+    multipliers: &[u64],
+    max_iters: usize,
+) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if is_perfect_square(target) {
+        let r = target.sqrt();
+        return if r > T::one() { Some(r) } else { None };
+    }
+
+    for &k in multipliers {
+        let mul_target = match T::from_u64(k).and_then(|k| target.checked_mul(&k)) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        // SQUFOF can't proceed on a square, extract the factor directly
+        if is_perfect_square(&mul_target) {
+            let g = target.gcd(&mul_target.sqrt());
+            if g > T::one() && &g < target {
+                return Some(g);
+            }
+            continue;
+        }
+        if let (Some(p), _) = squfof(target, mul_target, max_iters) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+/// Find a fraction `p/q` such that `p = residue * q (mod modulus)` with `p <= num_bound` and
+/// `0 < q <= den_bound`, using the extended Euclidean algorithm (rational reconstruction).
+///
+/// The result is unique if `2 * num_bound * den_bound < modulus`. Only non-negative fractions are
+/// reconstructed, a negative fraction `-p/q` can be recovered from `modulus - residue`.
+/// Returns [None] if no such fraction in lowest terms exists.
+pub fn rational_reconstruct<T: PrimalityBase>(
+    residue: &T,
+    modulus: &T,
+    num_bound: &T,
+    den_bound: &T,
+) -> Option<(T, T)>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if modulus.is_zero() {
+        return None;
+    }
+
+    // the remainders r_i and the magnitudes of the coefficients t_i, where r_i = t_i * residue
+    // and the signs of t_i alternate
+    let (mut r0, mut r1) = (modulus.clone(), residue % modulus);
+    let (mut t0, mut t1) = (T::zero(), T::one());
+    let mut positive = true;
+    while &r1 > num_bound {
+        let (q, r2) = r0.div_rem(&r1);
+        let t2 = t0 + &q * &t1;
+        r0 = std::mem::replace(&mut r1, r2);
+        t0 = std::mem::replace(&mut t1, t2);
+        positive = !positive;
+    }
+
+    if &t1 > den_bound || !r1.gcd(&t1).is_one() || (!positive && !r1.is_zero()) {
+        None
+    } else {
+        Some((r1, t1))
+    }
+}
+
+/// Test if an integer is a Carmichael number, i.e. a composite n such that `a^(n-1) = 1 (mod n)`
+/// for all a coprime to n.
+///
+/// This uses Korselt's criterion: n is square-free and `p - 1 | n - 1` for every prime factor p.
+pub fn is_carmichael(n: u64) -> bool {
+    // Carmichael numbers are odd and have at least three prime factors
+    if n < 561 || n.is_multiple_of(2) || is_prime64(n) {
+        return false;
+    }
+    let factors = factorize64(n);
+    factors.len() >= 3
+        && factors
+            .iter()
+            .all(|(&p, &e)| e == 1 && (n - 1).is_multiple_of(p - 1))
+}
+
+/// Test if an integer is an absolute Fermat pseudoprime, i.e. a composite that passes the Fermat
+/// test ([is_prp][PrimalityUtils::is_prp]) for every base coprime to it. These are exactly
+/// the Carmichael numbers.
+///
+/// For small n the Fermat condition is checked for all the bases directly, otherwise
+/// [is_carmichael] (Korselt's criterion) is used.
+pub fn is_absolute_fermat_pseudoprime(n: u64) -> bool {
+    const DIRECT_LIMIT: u64 = 1 << 16;
+
+    if n < DIRECT_LIMIT {
+        n > 3
+            && !is_prime64(n)
+            && (2..n)
+                .filter(|a| num_integer::gcd(*a, n) == 1)
+                .all(|a| a.powm(n - 1, &n) == 1)
+    } else {
+        is_carmichael(n)
+    }
+}
+
+/// Calculate the sum of the digits of n in the given base.
+///
+/// # Panics
+/// if base is less than 2.
+pub fn digit_sum(n: u64, base: u64) -> u64 {
+    assert!(base >= 2, "base must be at least 2");
+    let (mut n, mut sum) = (n, 0);
+    while n > 0 {
+        sum += n % base;
+        n /= base;
+    }
+    sum
+}
+
+/// Calculate the digital root of n in the given base, i.e. the single digit obtained by
+/// repeatedly applying [digit_sum].
+///
+/// # Panics
+/// if base is less than 2.
+pub fn digital_root(n: u64, base: u64) -> u64 {
+    assert!(base >= 2, "base must be at least 2");
+    // digit sums preserve the residue modulo (base - 1)
+    if n == 0 {
+        0
+    } else {
+        1 + (n - 1) % (base - 1)
+    }
+}
+
+/// Calculate `base^exp mod modulus` where the exponent can be negative. For a negative
+/// exponent, the result is `(base^-1)^|exp|`, and [None] is returned if the modular inverse
+/// of base doesn't exist.
+///
+/// # Panics
+/// if modulus is zero.
+pub fn powm_signed<T: PrimalityBase>(base: &T, exp: i64, modulus: &T) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!modulus.is_zero(), "modulus is zero");
+    let base = if exp < 0 {
+        (base % modulus).invm(modulus)?
+    } else {
+        base % modulus
+    };
+
+    let e = exp.unsigned_abs();
+    if let Some(e) = T::from_u64(e) {
+        return Some(base.powm(&e, modulus));
+    }
+
+    // the exponent doesn't fit in T, fall back to square-and-multiply
+    let (mut result, mut b, mut e) = (T::one() % modulus, base, e);
+    while e > 0 {
+        if e & 1 == 1 {
+            result = result.mulm(&b, modulus);
+        }
+        b = b.clone().mulm(&b, modulus);
+        e >>= 1;
+    }
+    Some(result)
+}
+
+/// List the odd composites below `limit` that pass the Baillie-PSW test (a strong probable
+/// prime test to base 2 followed by a strong Lucas probable prime test).
+///
+/// The composites are enumerated with a sieve of Eratosthenes and each one is tested, so this
+/// takes O(limit) memory and time. It's meant for validating the primality tests, and no
+/// counterexample is known below 2^64, so the result is expected to be always empty.
+pub fn bpsw_pseudoprimes_below(limit: u64) -> Vec<u64> {
+    odd_composites_below(limit)
+        .filter(|n| n.is_sprp(2) && n.is_slprp(None, None))
+        .collect()
+}
+
+/// Iterate over the odd composites below the limit, found by a sieve of the odd numbers
+fn odd_composites_below(limit: u64) -> impl Iterator<Item = u64> {
+    // composite[i] marks 2i+1
+    let size = (limit / 2) as usize;
+    let mut composite = vec![false; size];
+    let mut i = 1;
+    while (2 * i + 1) * (2 * i + 1) < limit as usize {
+        if !composite[i] {
+            let p = 2 * i + 1;
+            let mut j = p * p / 2;
+            while j < size {
+                composite[j] = true;
+                j += p;
+            }
+        }
+        i += 1;
+    }
+
+    composite
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, c)| c)
+        .map(|(i, _)| 2 * i as u64 + 1)
+}
+
+/// Calculate the summatory function of ω(n) (the number of distinct prime factors),
+/// i.e. `Σ_{k≤limit} ω(k)`.
+///
+/// Instead of factorizing each k, this counts the multiples of each prime, which gives
+/// `Σ_{p≤limit} floor(limit/p)`. The sum grows like `limit * ln(ln(limit))`.
+pub fn omega_sum(limit: u64) -> u64 {
+    PRIME_CACHE.with(|pb| pb.borrow_mut().primes(limit).map(|p| limit / p).sum())
+}
+
+/// Calculate the summatory function of Ω(n) (the number of prime factors counted with
+/// multiplicity), i.e. `Σ_{k≤limit} Ω(k)`.
+///
+/// Similar to [omega_sum], this counts the multiples of each prime power, which gives
+/// `Σ_{p^e≤limit} floor(limit/p^e)`.
+pub fn big_omega_sum(limit: u64) -> u64 {
+    PRIME_CACHE.with(|pb| {
+        let mut sum = 0;
+        for &p in pb.borrow_mut().primes(limit) {
+            let mut pe = Some(p);
+            while let Some(q) = pe.filter(|&q| q <= limit) {
+                sum += limit / q;
+                pe = q.checked_mul(p);
+            }
+        }
+        sum
+    })
+}
+
+/// Find the least prime p such that `p = a (mod m)`.
+///
+/// If `gcd(a, m) > 1`, the only possible prime is `a mod m` itself, and [None] is returned
+/// if it's not a prime. [None] is also returned if there's no such prime in the u64 range.
+pub fn least_prime_in_ap(a: u64, m: u64) -> Option<u64> {
+    let first = if m == 0 { a } else { a % m };
+    primes_in_ap(first, m, 1).first().copied()
+}
+
+/// Convert a u64 integer into the integer type T, returns [None] if the value
+/// exceeds the range of T.
+///
+/// This is the non-panicking counterpart of `T::from_u64(v).unwrap()`.
+#[inline]
+pub fn try_from_u64<T: PrimalityBase>(v: u64) -> Option<T> {
+    T::from_u64(v)
+}
+
+/// Factorize an integer and record how each composite is split, as a [FactorTree].
+///
+/// Small factors are split off by trial division first, the other composites are split by the
+/// divisor found with [PrimeBufferExt::divisor()][crate::buffer::PrimeBufferExt::divisor()].
+/// The leaves of the tree are the prime factors, and the composites on which the factorization
+/// failed are left as [FactorTree::Unfactored].
+pub fn factor_tree<T: PrimalityBase>(
+    target: T,
+    config: Option<FactorizationConfig>,
+) -> FactorTree<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    fn split<T: PrimalityBase>(
+        pb: &NaiveBuffer,
+        target: T,
+        config: FactorizationConfig,
+    ) -> FactorTree<T>
+    where
+        for<'r> &'r T: PrimalityRefBase<T>,
+    {
+        if target.is_zero() || target.is_one() {
+            return FactorTree::Unfactored(target);
+        }
+        let prime_config = Some(config.primality_config);
+        if pb.is_prime(&target, prime_config).probably() {
+            return FactorTree::Prime(target);
+        }
+
+        let small = SMALL_PRIMES
+            .iter()
+            .filter_map(|&p| try_from_u64::<T>(p as u64))
+            .find(|p| (&target % p).is_zero());
+        let divisor = small.or_else(|| pb.divisor(&target, &mut { config }));
+
+        match divisor {
+            Some(d) => {
+                let cofactor = &target / &d;
+                FactorTree::Composite {
+                    value: target,
+                    divisor: Box::new(split(pb, d, config)),
+                    cofactor: Box::new(split(pb, cofactor, config)),
+                }
+            }
+            None => FactorTree::Unfactored(target),
+        }
+    }
+
+    let pb = NaiveBuffer::new();
+    split(&pb, target, config.unwrap_or_default())
+}
+
+/// Sieve the values of Euler's totient function φ(k) for k ≤ limit
+fn totient_sieve(limit: usize) -> Vec<u64> {
+    let mut phi: Vec<u64> = (0..=limit as u64).collect();
+    for p in 2..=limit {
+        if phi[p] == p as u64 {
+            // p is a prime
+            for m in (p..=limit).step_by(p) {
+                phi[m] -= phi[m] / p as u64;
+            }
+        }
+    }
+    phi
+}
+
+/// Sieve the values of the Möbius function μ(k) for k ≤ limit
+fn moebius_sieve(limit: usize) -> Vec<i8> {
+    let mut mu = vec![1i8; limit + 1];
+    let mut is_composite = vec![false; limit + 1];
+    for p in 2..=limit {
+        if !is_composite[p] {
+            for m in (p..=limit).step_by(p) {
+                is_composite[m] = m > p;
+                mu[m] = -mu[m];
+            }
+            for m in (p.saturating_mul(p)..=limit).step_by(p * p) {
+                mu[m] = 0;
+            }
+        }
+    }
+    mu
+}
+
+/// Sieve the values of Ω(k) (the number of prime factors counted with multiplicity) for k ≤ limit
+fn big_omega_sieve(limit: usize) -> Vec<u8> {
+    let mut omega = vec![0u8; limit + 1];
+    for p in 2..=limit {
+        if omega[p] == 0 {
+            // p is a prime, count the multiples of each power of p
+            let mut pe = Some(p);
+            while let Some(q) = pe.filter(|&q| q <= limit) {
+                for m in (q..=limit).step_by(q) {
+                    omega[m] += 1;
+                }
+                pe = q.checked_mul(p);
+            }
+        }
+    }
+    omega
+}
+
+/// Find all the k-almost-primes not larger than the limit, i.e. the integers n with exactly k
+/// prime factors counted with multiplicity (Ω(n) = k), in ascending order.
+///
+/// The values of Ω are sieved over the range instead of factorizing each integer, so this
+/// takes O(limit) memory. With k = 1 these are the primes, and k = 2 gives the semiprimes.
+/// The only 0-almost-prime is 1.
+pub fn k_almost_primes(k: usize, limit: u64) -> Vec<u64> {
+    // the smallest k-almost-prime is 2^k
+    if k >= 64 || limit < 1 << k {
+        return Vec::new();
+    }
+    big_omega_sieve(limit as usize)
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .filter(|&(_, omega)| omega as usize == k)
+        .map(|(n, _)| n as u64)
+        .collect()
+}
+
+/// Calculate the summatory function of Euler's totient function Φ(n) = Σ_{k≤n} φ(k),
+/// by sieving all the totients up to n.
+///
+/// This takes O(n) memory, see [totient_sum_fast] for large n.
+pub fn totient_sum(n: u64) -> u128 {
+    totient_sieve(n as usize)
+        .into_iter()
+        .map(|v| v as u128)
+        .sum()
+}
+
+/// Calculate the summatory function of Euler's totient function Φ(n) = Σ_{k≤n} φ(k),
+/// using the recursive identity `Φ(n) = n(n+1)/2 - Σ_{d=2}^{n} Φ(floor(n/d))`.
+///
+/// The values of Φ up to n^(2/3) are sieved, and the larger values are memoized on the distinct
+/// values of floor(n/d), which gives roughly O(n^(2/3)) time and memory.
+pub fn totient_sum_fast(n: u64) -> u128 {
+    const THRESHOLD_SIEVE: u64 = 1 << 12;
+    if n <= THRESHOLD_SIEVE {
+        return totient_sum(n);
+    }
+
+    // prefix sums of the sieved totients
+    let limit = ((n as f64).powf(2. / 3.) as u64).clamp(THRESHOLD_SIEVE, n);
+    let small: Vec<u128> = totient_sieve(limit as usize)
+        .into_iter()
+        .scan(0u128, |acc, v| {
+            *acc += v as u128;
+            Some(*acc)
+        })
+        .collect();
+
+    // large[i] = Φ(floor(n/i)) for floor(n/i) > limit, computed from the smallest value
+    let count = (n / limit) as usize;
+    let mut large = vec![0u128; count + 1];
+    for i in (1..=count).rev() {
+        let v = n / i as u64;
+        if v <= limit {
+            large[i] = small[v as usize];
+            continue;
+        }
+
+        let mut sum = v as u128 * (v as u128 + 1) / 2;
+        let mut d = 2;
+        while d <= v {
+            // all d in [d, d_next) share the same quotient q
+            let q = v / d;
+            let d_next = v / q + 1;
+            let phi_q = if q <= limit {
+                small[q as usize]
+            } else {
+                // floor(n/(i*d)) = floor(v/d) = q
+                large[i * d as usize]
+            };
+            sum -= (d_next - d) as u128 * phi_q;
+            d = d_next;
+        }
+        large[i] = sum;
+    }
+    large[1]
+}
+
+/// Get the first n Ramanujan primes, where the k-th Ramanujan prime R_k is the smallest integer
+/// such that `π(x) - π(x/2) ≥ k` for all x ≥ R_k.
+///
+/// The counts `π(x) - π(x/2)` are tracked while sieving up to p_3n, which is an upper bound
+/// of R_n (Laishram, 2010).
+pub fn ramanujan_primes(n: usize) -> Vec<u64> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let primes = NaiveBuffer::new().into_nprimes(3 * n);
+    let bound = *primes.as_slice().last().unwrap() as usize;
+    let mut is_prime = vec![false; bound + 1];
+    for p in primes {
+        is_prime[p as usize] = true;
+    }
+
+    // last[c] is the largest x below the bound with π(x) - π(x/2) = c
+    let mut last = vec![0; n];
+    let (mut pi, mut pi_half) = (0usize, 0usize);
+    for x in 1..bound {
+        if is_prime[x] {
+            pi += 1;
+        }
+        if x % 2 == 0 && is_prime[x / 2] {
+            pi_half += 1;
+        }
+        if let Some(l) = last.get_mut(pi - pi_half) {
+            *l = x;
+        }
+    }
+
+    // R_k is one plus the largest x with π(x) - π(x/2) < k
+    last.iter()
+        .scan(0, |max, &x| {
+            *max = x.max(*max);
+            Some(*max as u64 + 1)
+        })
+        .collect()
+}
+
+/// Count the representations of n as an ordered sum of two squares `n = a^2 + b^2`, where
+/// a and b can be negative or zero (i.e. the number of lattice points on the circle of radius √n).
+///
+/// It's calculated from the factorization as `r2(n) = 4 (d1(n) - d3(n))`, where d1 and d3 count the
+/// divisors congruent to 1 and 3 modulo 4 respectively.
+pub fn r2(n: u64) -> u64 {
+    if n == 0 {
+        return 1;
+    }
+
+    let mut count = 4;
+    for (p, e) in factorize64(n) {
+        match p % 4 {
+            1 => count *= e as u64 + 1,
+            3 if e % 2 == 1 => return 0,
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Faillible factorization that only extracts the prime factors with at most `max_factor_bits`
+/// bits, and the cofactor consisting of larger prime factors (or composites that failed to be
+/// split) is returned intact.
+///
+/// The effort is limited by the bound: if it's at most 16 bits, all the prime factors within
+/// the bound are found by trial division and the cofactor is not tested further. Otherwise the
+/// composites are split by Pollard's rho method with about `2^(max_factor_bits/2)` iterations in
+/// each of the `config.rho_trials` trials, which is enough to find factors within the bound.
+///
+/// The product of the found factors and the returned cofactor (if any) equals to the target.
+pub fn factors_up_to_size<T: PrimalityBase>(
+    target: T,
+    max_factor_bits: usize,
+    config: Option<FactorizationConfig>,
+) -> (BTreeMap<T, usize>, Option<T>)
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    const TRIAL_BITS: usize = 16;
+    let mut result = BTreeMap::new();
+    if target.is_zero() {
+        return (result, Some(target));
+    }
+
+    let mut rest = T::one();
+    if max_factor_bits <= TRIAL_BITS {
+        let limit = (1u64 << max_factor_bits) - 1;
+        rest = PRIME_CACHE.with(|pb| {
+            let mut n = target;
+            for &p in pb.borrow_mut().primes(limit) {
+                let p: T = match try_from_u64(p) {
+                    Some(p) => p,
+                    None => break,
+                };
+                let mut exp = 0;
+                while (&n % &p).is_zero() {
+                    n = n / &p;
+                    exp += 1;
+                }
+                if exp > 0 {
+                    result.insert(p, exp);
+                }
+            }
+            n
+        });
+    } else {
+        let config = config.unwrap_or_default();
+        let max_iter = 1usize << (max_factor_bits / 2 + 2).min(20);
+        let pb = NaiveBuffer::new();
+        let mut todo = vec![target];
+        while let Some(n) = todo.pop() {
+            if n.is_one() {
+                continue;
+            }
+            if pb.is_prime(&n, Some(config.primality_config)).probably() {
+                if n.bits() <= max_factor_bits {
+                    *result.entry(n).or_insert(0) += 1;
+                } else {
+                    rest = rest * n;
+                }
+                continue;
+            }
+
+            let small = SMALL_PRIMES
+                .iter()
+                .filter_map(|&p| try_from_u64::<T>(p as u64))
+                .find(|p| (&n % p).is_zero());
+            let divisor = small
+                .or_else(|| is_perfect_square(&n).then(|| n.sqrt()))
+                .or_else(|| {
+                    (0..config.rho_trials).find_map(|_| {
+                        let (start, offset) = (random_below(&n), random_below(&n));
+                        pollard_rho(&n, start, offset, max_iter).0
+                    })
+                });
+            match divisor {
+                Some(d) => {
+                    todo.push(&n / &d);
+                    todo.push(d);
+                }
+                None => rest = rest * n,
+            }
+        }
+    }
+
+    (result, if rest.is_one() { None } else { Some(rest) })
+}
+
+/// Precomputed data for [prime_pi_lehmer()]
+struct LehmerContext {
+    /// primes up to the bound, π(x) is looked up directly for x ≤ bound
+    primes: Vec<u64>,
+    bound: u64,
+    /// φ(x, 6) for x < 2*3*5*7*11*13, the partial sieve function is periodic in the primorial
+    phi_table: Vec<u32>,
+}
+
+impl LehmerContext {
+    const PHI_TABLE_PRIMES: usize = 6;
+    const PHI_TABLE_SIZE: usize = 30030;
+
+    fn new(limit: u64) -> Self {
+        let bound = ((limit as f64).powf(2. / 3.) as u64).max(1 << 16);
+        let primes: Vec<u64> = NaiveBuffer::new().into_primes(bound).collect();
+
+        let mut phi_table = Vec::with_capacity(Self::PHI_TABLE_SIZE);
+        let mut count = 0;
+        for x in 0..Self::PHI_TABLE_SIZE as u64 {
+            if primes[..Self::PHI_TABLE_PRIMES].iter().all(|p| x % p != 0) {
+                count += 1;
+            }
+            phi_table.push(count);
+        }
+        LehmerContext {
+            primes,
+            bound,
+            phi_table,
+        }
+    }
+
+    /// Legendre's partial sieve function φ(x, a), the count of integers in [1, x] that are not
+    /// divisible by any of the first a primes
+    fn phi(&self, x: u64, a: usize) -> u64 {
+        if a == Self::PHI_TABLE_PRIMES {
+            let size = Self::PHI_TABLE_SIZE as u64;
+            let period = *self.phi_table.last().unwrap() as u64;
+            return (x / size) * period + self.phi_table[(x % size) as usize] as u64;
+        }
+        if x < self.primes[a] {
+            return (x > 0) as u64;
+        }
+        if x <= self.bound && x < self.primes[a] * self.primes[a] {
+            // the remaining integers are 1 and the primes larger than p_a
+            return self.pi(x) - a as u64 + 1;
+        }
+        self.phi(x, a - 1) - self.phi(x / self.primes[a - 1], a - 1)
+    }
+
+    fn pi(&self, x: u64) -> u64 {
+        if x <= self.bound {
+            return self.primes.partition_point(|&p| p <= x) as u64;
+        }
+
+        let a = self.pi(x.nth_root(4));
+        let b = self.pi(x.sqrt());
+        let c = self.pi(x.cbrt());
+        let mut sum = self.phi(x, a as usize) + (b + a - 2) * (b - a + 1) / 2;
+        for i in a + 1..=b {
+            let w = x / self.primes[i as usize - 1];
+            sum -= self.pi(w);
+            if i <= c {
+                let bi = self.pi(w.sqrt());
+                for j in i..=bi {
+                    sum -= self.pi(w / self.primes[j as usize - 1]) - (j - 1);
+                }
+            }
+        }
+        sum
+    }
+}
+
+/// Calculate the prime π function with Lehmer's formula.
+///
+/// The partial sieve function φ(x, a) is evaluated recursively, with a precomputed table for
+/// the first six primes and the π values up to limit^(2/3) looked up from sieved primes.
+/// This is much faster than sieving for large limits (e.g. around 10^10).
+pub fn prime_pi_lehmer(limit: u64) -> u64 {
+    LehmerContext::new(limit).pi(limit)
+}
+
+/// Find the prime constellations with the given offset pattern, i.e. all the primes p ≤ limit
+/// such that `p + offset` is a prime for every offset in the pattern. Each match is returned
+/// as the list of `p + offset`.
+///
+/// For example, the pattern `[0, 2]` gives the twin primes and `[0, 2, 6, 8]` gives the
+/// prime quadruplets.
+pub fn prime_constellations(pattern: &[u64], limit: u64) -> Vec<Vec<u64>> {
+    PRIME_CACHE.with(|pb| {
+        pb.borrow_mut()
+            .primes(limit)
+            .filter_map(|&p| {
+                pattern
+                    .iter()
+                    .map(|&o| p.checked_add(o).filter(|&q| is_prime64(q)))
+                    .collect()
+            })
+            .collect()
+    })
+}
+
+/// Calculate the exponent of the prime `p` in `n!` with Legendre's formula, i.e.
+/// `floor(n/p) + floor(n/p^2) + ...`. It takes `O(log_p(n))` divisions.
+///
+/// The primality of `p` is not checked, the result is the sum above for any `p > 1`.
+///
+/// # Panics
+/// if p is smaller than 2
+pub fn factorial_prime_exponent(p: u64, n: u64) -> u64 {
+    assert!(p > 1, "the base should be a prime");
+    let (mut q, mut count) = (n, 0);
+    while q > 0 {
+        q /= p;
+        count += q;
+    }
+    count
+}
+
+/// Factorize `n!` without computing it. The exponent of each prime `p <= n` is given by
+/// [factorial_prime_exponent()], and the primes are taken from the thread-local prime buffer.
+pub fn factorial_factorization(n: u64) -> BTreeMap<u64, usize> {
+    PRIME_CACHE.with(|pb| {
+        pb.borrow_mut()
+            .primes(n)
+            .map(|&p| (p, factorial_prime_exponent(p, n) as usize))
+            .collect()
+    })
+}
+
+/// Factorize the binomial coefficient `C(n, k)` without computing it. The exponent of each
+/// prime `p <= n` is the difference of the exponents in `n!`, `k!` and `(n-k)!`, see
+/// [factorial_prime_exponent()]. Primes with zero exponent are omitted.
+///
+/// # Panics
+/// if k is larger than n
+pub fn binomial_factorization(n: u64, k: u64) -> BTreeMap<u64, usize> {
+    assert!(k <= n, "k should not be larger than n");
+    PRIME_CACHE.with(|pb| {
+        pb.borrow_mut()
+            .primes(n)
+            .map(|&p| {
+                let e = factorial_prime_exponent(p, n)
+                    - factorial_prime_exponent(p, k)
+                    - factorial_prime_exponent(p, n - k);
+                (p, e as usize)
+            })
+            .filter(|&(_, e)| e > 0)
+            .collect()
+    })
+}
+
+/// Calculate the Kempner (Smarandache) function S(n), i.e. the smallest m such that n divides m!.
+///
+/// For each prime power p^e in the factorization, the smallest multiple of p whose factorial
+/// contains p^e is found, and S(n) is the maximum of them. By convention S(1) = 1.
+///
+/// # Panics
+/// if n is zero.
+pub fn smarandache<T: PrimalityBase>(n: &T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "Kempner function of zero is undefined");
+    let mut result = T::one();
+    for (p, e) in factorize(n.clone()) {
+        // walk through m = p, 2p, 3p, ... and accumulate the exponent of p in m!
+        let (mut k, mut count) = (T::zero(), 0usize);
+        while count < e {
+            k = k + T::one();
+            count += 1;
+            let mut q = k.clone();
+            while (&q % &p).is_zero() {
+                q = q / &p;
+                count += 1;
+            }
+        }
+        let m = k * &p;
+        if m > result {
+            result = m;
+        }
+    }
+    result
+}
+
+/// Test if an integer is a practical number, i.e. every smaller positive integer can be
+/// written as a sum of distinct divisors of it.
+///
+/// With the prime factors sorted as p_1 < ... < p_k, n is practical if and only if
+/// `p_i <= 1 + σ(p_1^e_1 * ... * p_(i-1)^e_(i-1))` for every i. Zero is not practical.
+pub fn is_practical<T: PrimalityBase>(n: &T) -> bool
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if n.is_zero() {
+        return false;
+    }
+    let mut prefix = T::one();
+    for (p, e) in factorize(n.clone()) {
+        if p > divisor_sigma(&prefix, 1) + T::one() {
+            return false;
+        }
+        prefix = prefix * num_traits::pow(p, e);
+    }
+    true
+}
+
+/// Get the exponents of n over a factor base, i.e. `v` such that `n = ∏ factor_base[i]^v[i]`.
+/// It returns `None` if n is not smooth over the factor base (it has a factor outside the base).
+///
+/// The factor base is expected to consist of distinct primes, entries less than 2 always get
+/// a zero exponent. This is the relation collection step of the sieving factorization methods.
+pub fn exponent_vector<T: PrimalityBase>(n: &T, factor_base: &[T]) -> Option<Vec<u32>>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if n.is_zero() {
+        return None;
+    }
+    let mut rest = n.clone();
+    let mut exps = Vec::with_capacity(factor_base.len());
+    for p in factor_base {
+        let mut e = 0;
+        if p > &T::one() {
+            while (&rest % p).is_zero() {
+                rest = rest / p;
+                e += 1;
+            }
+        }
+        exps.push(e);
+    }
+    if rest.is_one() {
+        Some(exps)
+    } else {
+        None
+    }
+}
+
+/// Find a nontrivial factor of `target` with the continued fraction factorization method (CFRAC).
+///
+/// The continued fraction expansion of sqrt(n) produces convergent numerators `A` with
+/// `A^2 = ±Q (mod n)` where Q < 2*sqrt(n). The relations with Q smooth over a factor base of
+/// `factor_base_size` primes (those with n as a quadratic residue) are collected, and a
+/// congruence of squares is found by Gaussian elimination mod 2. Returns [None] if no factor
+/// is found within a bounded number of iterations, which is always the case for primes.
+pub fn cfrac<T: PrimalityBase>(target: &T, factor_base_size: usize) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    const MAX_ITERS: usize = 1 << 22;
+    const EXTRA_RELATIONS: usize = 16;
+
+    let two = T::from_u8(2).unwrap();
+    if target <= &two {
+        return None;
+    }
+    if target.is_even() {
+        return Some(two);
+    }
+    let a0 = target.sqrt();
+    if &a0 * &a0 == *target {
+        return Some(a0);
+    }
+
+    // factor base with primes p such that (n/p) = 1, -1 is taken as an extra column
+    let mut base: Vec<T> = vec![two];
+    let count = 4 * factor_base_size + 16;
+    let candidates: Vec<u64> =
+        PRIME_CACHE.with(|pb| pb.borrow_mut().nprimes(count).cloned().collect());
+    for p in candidates.into_iter().skip(1) {
+        if base.len() >= factor_base_size {
+            break;
+        }
+        // primes larger than the target never divide the residues
+        let pt: T = match try_from_u64(p) {
+            Some(pt) => pt,
+            None => break,
+        };
+        let r = (target % &pt).to_u64().unwrap();
+        if r == 0 {
+            return if &pt < target { Some(pt) } else { None };
+        }
+        if r.jacobi(&p) == 1 {
+            base.push(pt);
+        }
+    }
+
+    // collect relations (A mod n, exponents of Q, sign of Q)
+    let mut relations: Vec<(T, Vec<u32>, bool)> = Vec::new();
+    let (mut m, mut d, mut a) = (T::zero(), T::one(), a0.clone());
+    let (mut prev_num, mut num) = (T::one(), &a0 % target);
+    for k in 1..MAX_ITERS {
+        if relations.len() > base.len() + EXTRA_RELATIONS {
+            break;
+        }
+        m = &d * &a - m;
+        d = (target - &m * &m) / d;
+        a = (&a0 + &m) / &d;
+
+        // num^2 = (-1)^k * d (mod n)
+        if let Some(exps) = exponent_vector(&d, &base) {
+            relations.push((num.clone(), exps, k % 2 == 1));
+        }
+        let next = a.clone().mulm(&num, target).addm(&prev_num, target);
+        prev_num = std::mem::replace(&mut num, next);
+    }
+
+    // Gaussian elimination mod 2, the history tracks the combination of relations in each row
+    let (cols, nrel) = (base.len() + 1, relations.len());
+    let mut rows: Vec<(Vec<u64>, Vec<u64>)> = relations
+        .iter()
+        .enumerate()
+        .map(|(i, (_, exps, neg))| {
+            let mut bits = vec![0u64; cols.div_ceil(64)];
+            let mut history = vec![0u64; nrel.div_ceil(64)];
+            bits[0] |= *neg as u64;
+            for (j, e) in exps.iter().enumerate() {
+                bits[(j + 1) / 64] |= ((e & 1) as u64) << ((j + 1) % 64);
+            }
+            history[i / 64] |= 1 << (i % 64);
+            (bits, history)
+        })
+        .collect();
+    let mut is_pivot = vec![false; nrel];
+    for c in 0..cols {
+        let (w, b) = (c / 64, 1u64 << (c % 64));
+        let pivot = match (0..nrel).find(|&i| !is_pivot[i] && rows[i].0[w] & b != 0) {
+            Some(i) => i,
+            None => continue,
+        };
+        is_pivot[pivot] = true;
+        let prow = rows[pivot].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != pivot && row.0[w] & b != 0 {
+                row.0.iter_mut().zip(&prow.0).for_each(|(x, y)| *x ^= y);
+                row.1.iter_mut().zip(&prow.1).for_each(|(x, y)| *x ^= y);
+            }
+        }
+    }
+
+    // each remaining row gives x^2 = y^2 (mod n)
+    let dependencies = rows.iter().zip(is_pivot).filter(|(_, p)| !p);
+    for ((_, history), _) in dependencies {
+        let mut x = T::one();
+        let mut total = vec![0u64; base.len()];
+        for (i, (num, exps, _)) in relations.iter().enumerate() {
+            if history[i / 64] & (1 << (i % 64)) != 0 {
+                x = x.mulm(num, target);
+                for (t, &e) in total.iter_mut().zip(exps) {
+                    *t += e as u64;
+                }
+            }
+        }
+        let mut y = T::one();
+        for (p, t) in base.iter().zip(total) {
+            // the exponent could exceed the range of T
+            let (mut e, mut b) = (t / 2, p.clone());
+            while e > 0 {
+                if e & 1 == 1 {
+                    y = y.mulm(&b, target);
+                }
+                b = b.sqm(target);
+                e >>= 1;
+            }
+        }
+
+        let diff = if x > y { x - y } else { y - x };
+        let g = diff.gcd(target);
+        if !g.is_one() && &g != target {
+            return Some(g);
+        }
+    }
+    None
+}
+
+/// Find an element with multiplicative order exactly `order` modulo the prime `p`, i.e. a
+/// primitive `order`-th root of unity, which is required by the number-theoretic transform.
+/// It's obtained by raising the smallest primitive root to the power of `(p - 1) / order`.
+///
+/// Returns [None] if p is not a prime or `order` doesn't divide `p - 1`.
+pub fn primitive_root_of_unity(p: u64, order: u64) -> Option<u64> {
+    if order == 0 || !is_prime64(p) || !(p - 1).is_multiple_of(order) {
+        return None;
+    }
+    let g = primitive_root(p)?;
+    Some(g.powm((p - 1) / order, &p))
+}
+
+/// Enumerate the divisors of n lazily in descending order, starting from n itself.
+///
+/// The divisors are generated with a max-heap, where each divisor is reached from a larger one
+/// by dividing out one prime with a nondecreasing index, so that the full divisor list is never
+/// materialized. This is useful when looking for the largest divisor with some property.
+///
+/// # Panics
+/// if n is zero.
+pub fn divisors_desc<T: PrimalityBase>(n: &T) -> impl Iterator<Item = T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    assert!(!n.is_zero(), "divisors of zero are undefined");
+    let primes: Vec<T> = factorize(n.clone()).into_keys().collect();
+    let mut heap = BinaryHeap::new();
+    heap.push((n.clone(), 0usize));
+    std::iter::from_fn(move || {
+        let (d, start) = heap.pop()?;
+        for (i, p) in primes.iter().enumerate().skip(start) {
+            if (&d % p).is_zero() {
+                heap.push((&d / p, i));
+            }
+        }
+        Some(d)
+    })
+}
+
+/// Find all the highly composite numbers up to the limit, i.e. the positive integers with
+/// more divisors than any smaller positive integer.
+///
+/// The exponents in the factorization of a highly composite number are nonincreasing over
+/// consecutive primes, so only such candidates are enumerated and then filtered by a running
+/// maximum of the divisor count σ_0 (see [divisor_sigma]).
+pub fn highly_composite(limit: u64) -> Vec<u64> {
+    // enumerate n = 2^e1 * 3^e2 * ... with e1 >= e2 >= ..., the stack holds
+    // (n, index of the next prime, max exponent of the next prime)
+    let mut candidates = Vec::new();
+    let mut stack = vec![(1u64, 0usize, u32::MAX)];
+    while let Some((n, i, max_exp)) = stack.pop() {
+        if n > limit {
+            continue;
+        }
+        candidates.push(n);
+        let p = SMALL_PRIMES[i] as u64;
+        let mut m = n;
+        for e in 1..=max_exp {
+            m = match m.checked_mul(p) {
+                Some(m) if m <= limit => m,
+                _ => break,
+            };
+            stack.push((m, i + 1, e));
+        }
+    }
+    candidates.sort_unstable();
+
+    let mut record = 0;
+    candidates
+        .into_iter()
+        .filter(|n| {
+            let tau = divisor_sigma(n, 0);
+            if tau > record {
+                record = tau;
+                true
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+/// Verify whether the given bases form a deterministic Miller-Rabin witness set below the bound.
+///
+/// Returns [None] if every odd composite below the bound fails the strong probable prime test
+/// for at least one of the bases, otherwise the smallest odd composite passing all the tests
+/// is returned as a counterexample. Bases that are multiples of the tested number are skipped
+/// as usual. This can be used to validate hashed or fixed witness tables, but it runs a sieve
+/// up to the bound, so it's only practical for moderate bounds.
+pub fn verify_witness_set(bases: &[u64], bound: u64) -> Option<u64> {
+    odd_composites_below(bound).find(|&n| bases.iter().all(|&b| b % n == 0 || n.is_sprp(b % n)))
+}
+
+/// Find the shortest prefix of the prime bases `[2, 3, 5, 7, ..., 37]` that correctly
+/// classifies n with strong probable prime tests.
+///
+/// If n is a prime, the returned bases are the first k primes, where k is the smallest count
+/// that is deterministic in the range of n (<https://oeis.org/A014233>), so every base passes
+/// the test. If n is composite, only the first base that witnesses the compositeness is returned.
+/// Bases that are multiples of n are skipped in the tests, as in [verify_witness_set()].
+/// An empty list is returned for 0 and 1.
+pub fn minimal_witness_set(n: u64) -> Vec<u64> {
+    // the first k + 1 primes are deterministic below PSI[k]
+    const PSI: [u64; 11] = [
+        2047,
+        1373653,
+        25326001,
+        3215031751,
+        2152302898747,
+        3474749660383,
+        341550071728321,
+        341550071728321,
+        3825123056546413051,
+        3825123056546413051,
+        3825123056546413051,
+    ];
+    if n < 2 {
+        return Vec::new();
+    }
+
+    // twelve prime bases are enough for all u64 integers
+    let bases: Vec<u64> = SMALL_PRIMES[..12].iter().map(|&p| p as u64).collect();
+    if let Some(&b) = bases.iter().find(|&&b| b % n != 0 && !n.is_sprp(b % n)) {
+        return vec![b];
+    }
+    let count = PSI.iter().position(|&psi| n < psi);
+    let count = count.map_or(bases.len(), |i| i + 1);
+    bases[..count].to_vec()
+}
+
+/// Multiply two polynomials in `Z_n[X]/(X^r - 1)`, where r is the length of the coefficients
+fn poly_mulm_cyclic<T: PrimalityBase>(a: &[T], b: &[T], n: &T) -> Vec<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let r = a.len();
+    let mut c = vec![T::zero(); r];
+    for (i, x) in a.iter().enumerate().filter(|(_, x)| !x.is_zero()) {
+        for (j, y) in b.iter().enumerate().filter(|(_, y)| !y.is_zero()) {
+            let k = (i + j) % r;
+            c[k] = (&c[k]).addm(&x.mulm(y, n), n);
+        }
+    }
+    c
+}
+
+/// Deterministic primality test with the Agrawal–Kayal–Saxena (AKS) algorithm.
+///
+/// It checks that n is not a perfect power, finds the smallest r such that the multiplicative
+/// order of n modulo r exceeds log2(n)^2, and then verifies `(X+a)^n = X^n + a` in
+/// `Z_n[X]/(X^r - 1)` for all the required a. The test runs in polynomial time but it's very
+/// slow in practice, so it's only meant as a reference to verify other tests.
+pub fn is_prime_aks<T: PrimalityBase>(n: &T) -> bool
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if n < &T::from_u8(2).unwrap() {
+        return false;
+    }
+
+    // the bit length is used as an upper bound of log2(n), which keeps the test valid
+    let log2n = n.bits() as u64;
+    if (2..=log2n as u32).any(|b| n.is_nth_power(b)) {
+        return false;
+    }
+
+    // find the smallest r such that ord_r(n) > log2(n)^2
+    let max_order = log2n * log2n;
+    let mut r = 2u64;
+    let nr = loop {
+        let nr = match try_from_u64::<T>(r) {
+            Some(rt) => (n % rt).to_u64().unwrap(),
+            None => n.to_u64().unwrap() % r,
+        };
+        if num_integer::gcd(nr, r) == 1 {
+            let mut x = 1u64;
+            if (0..max_order).all(|_| {
+                x = x.mulm(nr, &r);
+                x != 1
+            }) {
+                break nr;
+            }
+        }
+        r += 1;
+    };
+
+    // check for small factors
+    for a in 2..=r {
+        // a doesn't fit in T only if it's larger than n
+        let a: T = match try_from_u64(a) {
+            Some(a) => a,
+            None => return true,
+        };
+        if &a >= n {
+            return true;
+        }
+        if !a.gcd(n).is_one() {
+            return false;
+        }
+    }
+
+    // check (X+a)^n = X^(n mod r) + a
+    let a_limit = ((euler_phi(r) as f64).sqrt() * log2n as f64) as u64;
+    for a in 1..=a_limit {
+        let a = T::from_u64(a).unwrap() % n;
+        let mut lhs = vec![T::zero(); r as usize];
+        lhs[0] = T::one();
+        for i in (0..n.bits()).rev() {
+            lhs = poly_mulm_cyclic(&lhs, &lhs, n);
+            if n.bit(i) {
+                // multiply by X + a
+                lhs.rotate_right(1);
+                let shifted = lhs.clone();
+                for (k, c) in lhs.iter_mut().enumerate() {
+                    let prev = &shifted[(k + 1) % r as usize];
+                    *c = (&*c).addm(&prev.mulm(&a, n), n);
+                }
+            }
+        }
+
+        let mut rhs = vec![T::zero(); r as usize];
+        rhs[0] = a;
+        rhs[nr as usize] = (&rhs[nr as usize]).addm(&T::one(), n);
+        if lhs != rhs {
+            return false;
+        }
+    }
+    true
+}
+
+/// Calculate the first Chebyshev function θ(x) = Σ_{p≤x} ln(p)
+pub fn chebyshev_theta(x: u64) -> f64 {
+    PRIME_CACHE.with(|pb| pb.borrow_mut().primes(x).map(|&p| (p as f64).ln()).sum())
+}
+
+/// Calculate the second Chebyshev function ψ(x) = Σ_{p^k≤x} ln(p), i.e. the summatory
+/// function of the von Mangoldt function. The prime number theorem is equivalent to ψ(x) ~ x.
+pub fn chebyshev_psi(x: u64) -> f64 {
+    PRIME_CACHE.with(|pb| {
+        pb.borrow_mut()
+            .primes(x)
+            .map(|&p| {
+                // count the powers p^k <= x
+                let (mut k, mut pk) = (1u32, p);
+                while let Some(next) = pk.checked_mul(p).filter(|&v| v <= x) {
+                    pk = next;
+                    k += 1;
+                }
+                k as f64 * (p as f64).ln()
+            })
+            .sum()
+    })
+}
+
+/// Approximate the prime zeta function P(s) = Σ_p p^(-s) by the partial sum over the
+/// primes up to `terms_limit`.
+///
+/// The series converges for s > 1, and the truncation error is roughly
+/// `x^(1-s) / ((s-1) ln(x))` with `x = terms_limit`, which follows from the prime number
+/// theorem. So the convergence is quite slow for s close to 1.
+pub fn prime_zeta(s: f64, terms_limit: u64) -> f64 {
+    PRIME_CACHE.with(|pb| {
+        pb.borrow_mut()
+            .primes(terms_limit)
+            .map(|&p| (p as f64).powf(-s))
+            .sum()
+    })
+}
+
+/// Find the maximal prime gaps below `limit`, returned as `(gap, p)` pairs where `p` is the
+/// prime starting the gap. Each gap is larger than all gaps between smaller primes, and
+/// both ends of the gaps are not larger than `limit`.
+///
+/// The primes are taken from the thread-local prime buffer (see [primes()]).
+pub fn maximal_prime_gaps(limit: u64) -> Vec<(u64, u64)> {
+    PRIME_CACHE.with(|pb| {
+        let mut pb = pb.borrow_mut();
+        let mut iter = pb.primes(limit);
+        let mut last = match iter.next() {
+            Some(&p) => p,
+            None => return Vec::new(),
+        };
+
+        let mut records = Vec::new();
+        let mut max_gap = 0;
+        for &p in iter {
+            if p - last > max_gap {
+                max_gap = p - last;
+                records.push((max_gap, last));
+            }
+            last = p;
+        }
+        records
+    })
+}
+
+/// Calculate the summatory function of Jordan's totient function `Σ_{n≤limit} J_k(n)`, where
+/// `J_k(n)` counts the k-tuples of integers in `[1, n]` that are coprime with n together.
+/// J_1 is the Euler's totient function.
+///
+/// As `J_k = μ * id_k` (Dirichlet convolution), the sum equals `Σ_d μ(d) Σ_{m≤limit/d} m^k`,
+/// which is evaluated with a Möbius sieve and the prefix sums of the k-th powers. The
+/// arithmetic wraps around on overflow, so the result is only exact if it fits in u128.
+pub fn jordan_totient_sum(k: u32, limit: u64) -> u128 {
+    let n = limit as usize;
+    let mut power_sums = vec![0u128; n + 1];
+    for m in 1..=n {
+        power_sums[m] = power_sums[m - 1].wrapping_add((m as u128).wrapping_pow(k));
+    }
+
+    moebius_sieve(n)
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .fold(0u128, |acc, (d, mu)| match mu {
+            1 => acc.wrapping_add(power_sums[n / d]),
+            -1 => acc.wrapping_sub(power_sums[n / d]),
+            _ => acc,
+        })
+}
+
+/// Compute a coprime basis (GCD-free basis) of the given integers, i.e. a sorted list of
+/// pairwise coprime integers greater than one, such that every input is a product of powers
+/// of the basis elements. Zeros and ones in the input are ignored.
+///
+/// The basis is refined by repeatedly splitting two elements `a`, `b` sharing a factor into
+/// `gcd(a, b)`, `a / gcd(a, b)` and `b / gcd(a, b)`, so only GCDs are computed and no input
+/// needs to be factorized.
+pub fn coprime_basis<T: PrimalityBase>(values: &[T]) -> Vec<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let mut basis: Vec<T> = Vec::new();
+    let mut pending: Vec<T> = values.iter().filter(|v| !v.is_zero()).cloned().collect();
+    while let Some(a) = pending.pop() {
+        if a.is_one() {
+            continue;
+        }
+        let shared = basis
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (i, a.gcd(b)))
+            .find(|(_, g)| !g.is_one());
+        match shared {
+            Some((i, g)) => {
+                let b = basis.swap_remove(i);
+                pending.push(&a / &g);
+                pending.push(&b / &g);
+                pending.push(g);
+            }
+            None => basis.push(a),
+        }
+    }
+    basis.sort();
+    basis
+}
+
+/// Calculate the Kronecker symbol (a/n) for signed integers, which extends the Jacobi symbol
+/// to all integers n, including negative and even ones. The full definition is followed:
+///
+/// - `(a/0)` is 1 if a = ±1 and 0 otherwise,
+/// - `(a/-1)` is -1 if a < 0 and 1 otherwise,
+/// - `(a/2)` is 0 for even a, 1 if a = ±1 (mod 8) and -1 if a = ±3 (mod 8).
+///
+/// This is commonly used with (negative) discriminants of binary quadratic forms.
+#[cfg(feature = "num-bigint")]
+pub fn kronecker(a: &BigInt, n: &BigInt) -> i8 {
+    if n.sign() == Sign::NoSign {
+        return (a.magnitude() == &BigUint::from(1u8)) as i8;
+    }
+    let mut result = match (a.sign(), n.sign()) {
+        (Sign::Minus, Sign::Minus) => -1,
+        _ => 1,
+    };
+
+    // factor 2 from the modulus
+    let mut n = n.magnitude().clone();
+    let twos = n.trailing_zeros().unwrap();
+    if twos > 0 {
+        if !a.bit(0) {
+            return 0;
+        }
+        let a_mod8 = num_integer::Integer::mod_floor(a, &BigInt::from(8u8));
+        if twos % 2 == 1 && (a_mod8 == BigInt::from(3u8) || a_mod8 == BigInt::from(5u8)) {
+            result = -result;
+        }
+        n >>= twos;
+    }
+
+    // Jacobi symbol with the remaining odd modulus
+    let a = num_integer::Integer::mod_floor(a, &BigInt::from(n.clone()));
+    result * a.magnitude().jacobi(&n)
+}
+
+/// Calculate the class number h(d) of the imaginary quadratic order with discriminant d < 0,
+/// by counting the reduced primitive binary quadratic forms `ax^2 + bxy + cy^2` with
+/// `b^2 - 4ac = d`, i.e. `|b| <= a <= c`, `b >= 0` if `|b| = a` or `a = c`, and
+/// `gcd(a, b, c) = 1`.
+///
+/// Returns [None] if d is not a negative discriminant (d ≡ 0 or 1 mod 4). The enumeration
+/// takes O(|d|) time, so it's only suitable for small discriminants.
+pub fn class_number_naive(d: i64) -> Option<u64> {
+    if d >= 0 || !matches!(d.rem_euclid(4), 0 | 1) {
+        return None;
+    }
+    let d = d as i128;
+
+    let mut count = 0;
+    let mut a = 1i128;
+    while 3 * a * a <= -d {
+        // b has the same parity as d
+        let mut b = -a + 1;
+        if (b - d).rem_euclid(2) == 1 {
+            b += 1;
+        }
+        while b <= a {
+            let num = b * b - d;
+            if num % (4 * a) == 0 {
+                let c = num / (4 * a);
+                let reduced = c > a || (c == a && b >= 0);
+                if reduced && num_integer::gcd(num_integer::gcd(a, b), c) == 1 {
+                    count += 1;
+                }
+            }
+            b += 2;
+        }
+        a += 1;
+    }
+    Some(count)
+}
+
+/// Precompute a table for repeated modular exponentiations `base^exp mod modulus` with
+/// a fixed base and modulus, see [FixedBaseExp] for details.
+///
+/// # Panics
+/// if modulus is zero.
+pub fn powm_fixed_base<T: PrimalityBase>(base: &T, modulus: &T) -> FixedBaseExp<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    FixedBaseExp::new(base, modulus)
+}
+
+/// Calculate the greatest common divisor with the binary GCD algorithm (Stein's algorithm).
+///
+/// The algorithm only uses subtractions and shifts by the number of trailing zeros, so it
+/// can be faster than the division based Euclidean algorithm on platforms where the division
+/// is slow, or for multi-precision integers of similar sizes. By convention `gcd(0, 0) = 0`.
+pub fn binary_gcd<T: PrimalityBase>(a: T, b: T) -> T
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    if a.is_zero() {
+        return b;
+    }
+    if b.is_zero() {
+        return a;
+    }
+
+    // remove the common factors of 2, and make both a and b odd
+    let (za, zb) = (a.trailing_zeros(), b.trailing_zeros());
+    let shift = za.min(zb);
+    let mut a = &a >> za;
+    let mut b = &b >> zb;
+    loop {
+        if a > b {
+            std::mem::swap(&mut a, &mut b);
+        }
+        b = b - &a; // the difference of two odd numbers is even
+        if b.is_zero() {
+            break a * num_traits::pow(T::from_u8(2).unwrap(), shift);
+        }
+        b = &b >> b.trailing_zeros();
+    }
+}
+
+/// Find the largest gap between consecutive integers coprime to `modulus`, i.e. the Jacobsthal
+/// function `j(modulus)`, the smallest m such that every m consecutive integers contain one
+/// coprime to `modulus`. When the modulus is a primorial, this gives the lower bounds of the
+/// maximal prime gaps.
+///
+/// The gaps are periodic with the modulus, so the residues in `[1, modulus + 1]` are walked
+/// through, which takes `O(modulus)` GCD computations.
+///
+/// # Panics
+/// if modulus is zero.
+pub fn coprime_gap(modulus: u64) -> u64 {
+    assert!(modulus > 0, "the modulus should be positive");
+    let (mut last, mut gap) = (1u64, 1u64);
+    for k in 2..=modulus.saturating_add(1) {
+        if num_integer::gcd(k, modulus) == 1 {
+            gap = gap.max(k - last);
+            last = k;
+        }
+    }
+    gap
+}
+
+/// Test if p is a Wieferich prime, i.e. a prime such that `2^(p-1) = 1 (mod p^2)`.
+/// The only known Wieferich primes are 1093 and 3511. Returns false if p is not a prime.
+pub fn is_wieferich_prime(p: u64) -> bool {
+    if !is_prime64(p) {
+        return false;
+    }
+    let m = p as u128 * p as u128;
+    2u128.powm(p as u128 - 1, &m) == 1
+}
+
+/// Test if p is a Wilson prime, i.e. a prime such that `(p-1)! = -1 (mod p^2)`.
+/// The only known Wilson primes are 5, 13 and 563. Returns false if p is not a prime.
+///
+/// The factorial is evaluated by multiplying the terms modulo p^2, so it takes `O(p)` time.
+pub fn is_wilson_prime(p: u64) -> bool {
+    if !is_prime64(p) {
+        return false;
+    }
+    let m = p as u128 * p as u128;
+    let fac = (2..p as u128).fold(1u128, |acc, k| acc.mulm(k, &m));
+    fac == m - 1
+}
+
+/// Compute the modular inverses of all residues in `[0, m)`, [None] is returned for the
+/// residues that are not invertible.
+///
+/// If m is a prime, the table is filled in `O(m)` time with the recurrence
+/// `inv(i) = -(m / i) * inv(m mod i) (mod m)`, otherwise each residue is inverted separately.
+/// The returned vector has length `m`, so the modulus is limited to fit in `u32`.
+///
+/// # Panics
+/// if m is zero or it doesn't fit in `u32`
+pub fn inverse_table<T: PrimalityBase>(m: &T) -> Vec<Option<T>>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let m = m
+        .to_u32()
+        .expect("the modulus is too large for an inverse table") as u64;
+    assert!(m > 0, "the modulus should be positive");
+
+    let table: Vec<Option<u64>> = if is_prime64(m) {
+        let mut inv = vec![0u64; m as usize];
+        if m > 1 {
+            inv[1] = 1;
+        }
+        for i in 2..m {
+            let (q, r) = (m / i, m % i);
+            inv[i as usize] = (q * inv[r as usize] % m).negm(&m);
+        }
+        inv.into_iter()
+            .enumerate()
+            .map(|(i, v)| (i > 0).then_some(v))
+            .collect()
+    } else {
+        (0..m).map(|i| i.invm(&m)).collect()
+    };
+    table
+        .into_iter()
+        .map(|v| v.map(|v| T::from_u64(v).unwrap()))
+        .collect()
+}
+
+/// Sieve the primes in the window `[lo, hi]`, and return them as a packed bitmap.
+///
+/// Each integer in the window takes one bit: the integer `n` is represented by the bit
+/// `(n - lo) % 64` (counting from the least significant bit) of the word `(n - lo) / 64`,
+/// and the bit is set if and only if `n` is a prime. The unused bits in the last word are
+/// zeros. The result is empty if `lo > hi`.
+///
+/// The window is sieved with the primes up to `sqrt(hi)` from the thread-local prime buffer
+/// (see [primes()]), so both `hi - lo` and `sqrt(hi)` should be small enough to be sieved.
+pub fn prime_bitmap(lo: u64, hi: u64) -> Vec<u64> {
+    if lo > hi {
+        return Vec::new();
+    }
+    let len = (hi - lo) as usize + 1;
+    let mut bitmap = vec![u64::MAX; len.div_ceil(64)];
+    if !len.is_multiple_of(64) {
+        *bitmap.last_mut().unwrap() = (1 << (len % 64)) - 1;
+    }
+    let mut clear = |n: u64| {
+        let i = (n - lo) as usize;
+        bitmap[i / 64] &= !(1 << (i % 64));
+    };
+    (lo..=hi.min(1)).for_each(&mut clear);
+
+    PRIME_CACHE.with(|pb| {
+        for &p in pb.borrow_mut().primes(Roots::sqrt(&hi)) {
+            // start from the first multiple in the window, but skip p itself
+            let first = match lo.div_ceil(p).checked_mul(p) {
+                Some(m) => m.max(p * p),
+                None => continue,
+            };
+            for m in (first..=hi).step_by(p as usize) {
+                clear(m);
+            }
+        }
+    });
+    bitmap
+}
+
+/// Calculate the modular division `a * b^-1 mod m`, returns [None] if b is not invertible
+/// modulo m. [Modulus::div()][crate::Modulus::div] is a shortcut to this function, and
+/// [Mint::divm()][crate::detail::Mint::divm] does the division on integers in Montgomery form.
+pub fn divm<T: PrimalityBase>(a: &T, b: &T, m: &T) -> Option<T>
+where
+    for<'r> &'r T: PrimalityRefBase<T>,
+{
+    let b_inv = b.invm(m)?;
+    Some((a % m).mulm(&b_inv, m))
+}
+
+// TODO: More functions
+// REF: http://www.numbertheory.org/gnubc/bc_programs.html
+// REF: https://github.com/TilmanNeumann/java-math-library
+// - is_smooth: checks if the smoothness bound is at least b
+// - euler_phi: Euler's totient function
+// - jordan_tot: Jordan's totient function
+// Others include Louiville function, Mangoldt function, Dedekind psi function, Dickman rho function, etc..
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{prelude::SliceRandom, random};
+    use std::iter::FromIterator;
+
+    #[test]
+    fn is_prime64_test() {
+        // test small primes
+        for x in 2..100 {
+            assert_eq!(SMALL_PRIMES.contains(&x), is_prime64(x as u64));
+        }
+        assert!(is_prime64(677));
+        
+        // from PR #7
+        assert!(!is_prime64(9773));
+        assert!(!is_prime64(13357));
+        assert!(!is_prime64(18769));
+
+        // some large primes
+        assert!(is_prime64(6469693333));
+        assert!(is_prime64(13756265695458089029));
+        assert!(is_prime64(13496181268022124907));
+        assert!(is_prime64(10953742525620032441));
+        assert!(is_prime64(17908251027575790097));
+
+        // primes from examples in Bradley Berg's hash method
+        assert!(is_prime64(480194653));
+        assert!(!is_prime64(20074069));
+        assert!(is_prime64(8718775377449));
+        assert!(is_prime64(3315293452192821991));
+        assert!(!is_prime64(8651776913431));
+        assert!(!is_prime64(1152965996591997761));
+
+        // false positives reported by JASory (#4)
+        assert!(!is_prime64(600437059821397));
+        assert!(!is_prime64(3866032210719337));
+        assert!(!is_prime64(4100599722623587));
+
+        // ensure no factor for 100 random primes
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let x = random();
+            if !is_prime64(x) {
+                continue;
+            }
+            assert_ne!(x % (*SMALL_PRIMES.choose(&mut rng).unwrap() as u64), 0);
+        }
+
+        // create random composites
+        for _ in 0..100 {
+            let x = random::<u32>() as u64;
+            let y = random::<u32>() as u64;
+            assert!(!is_prime64(x * y));
+        }
+    }
+
+    #[test]
+    fn is_perfect_square_test() {
+        for n in 0..30000u64 {
+            let r = (n as f64).sqrt() as u64;
+            assert_eq!(is_perfect_square(&n), r * r == n, "{}", n);
+        }
+        for n in 0..=u8::MAX {
+            let expected = is_perfect_square(&(n as u64));
+            assert_eq!(is_perfect_square(&n), expected, "{}", n);
+        }
+        for n in 0..=u16::MAX {
+            let expected = is_perfect_square(&(n as u64));
+            assert_eq!(is_perfect_square(&n), expected, "{}", n);
+        }
+
+        let x = 104729u64 * 104729;
+        assert!(is_perfect_square(&x));
+        assert!(!is_perfect_square(&(x + 1)));
+        assert!(!is_perfect_square(&(x - 1)));
+        let x = (u64::MAX as u128) * (u64::MAX as u128);
+        assert!(is_perfect_square(&x));
+        assert!(!is_perfect_square(&(x - 1)));
+        assert!(!is_perfect_square(&u128::MAX));
+        for _ in 0..100 {
+            let r = random::<u64>() as u128;
+            assert!(is_perfect_square(&(r * r)));
+            assert!(!is_perfect_square(&(r * r + 2 * r + 2)));
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let r = BigUint::from(3u8).pow(100);
+            assert!(is_perfect_square(&(&r * &r)));
+            assert!(!is_perfect_square(&(&r * &r + 1u8)));
+        }
+    }
+
+    #[test]
+    fn is_prime64_deterministic_test() {
+        for x in 0..20000u64 {
+            assert_eq!(is_prime64_deterministic(x), is_prime64(x));
+        }
+
+        // strong pseudoprimes to the first prime bases (https://oeis.org/A014233)
+        let spsp = [
+            2047,
+            1373653,
+            25326001,
+            3215031751,
+            2152302898747,
+            3474749660383,
+            341550071728321,
+            3825123056546413051,
+        ];
+        for x in spsp {
+            assert!(!is_prime64_deterministic(x));
+        }
+
+        // same test vectors as is_prime64_test
+        let vectors = [
+            677,
+            9773,
+            13357,
+            18769,
+            6469693333,
+            13756265695458089029,
+            13496181268022124907,
+            10953742525620032441,
+            17908251027575790097,
+            480194653,
+            20074069,
+            8718775377449,
+            3315293452192821991,
+            8651776913431,
+            1152965996591997761,
+            600437059821397,
+            3866032210719337,
+            4100599722623587,
+        ];
+        for x in vectors {
+            assert_eq!(is_prime64_deterministic(x), is_prime64(x));
+        }
+        for _ in 0..1000 {
+            let x = random::<u64>();
+            assert_eq!(is_prime64_deterministic(x), is_prime64(x));
+            let x = random::<u32>() as u64;
+            assert_eq!(is_prime64_deterministic(x), is_prime64(x));
+        }
+    }
+
+    #[test]
+    fn factorize64_with_rng_test() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        for _ in 0..10 {
+            let x = random::<u64>();
+            let seed = random::<u64>();
+            let (mut rng1, mut rng2) = (StdRng::seed_from_u64(seed), StdRng::seed_from_u64(seed));
+            let fac = factorize64_with_rng(x, &mut rng1);
+            assert_eq!(fac, factorize64(x));
+            assert_eq!(factorize64_with_rng(x, &mut rng2), fac);
+
+            // the same random numbers are consumed
+            assert_eq!(rng1.gen::<u64>(), rng2.gen::<u64>());
+        }
+
+        // the random source is used by Pollard's rho method on a hard semiprime
+        let mut rng = StdRng::seed_from_u64(0);
+        let fac = factorize64_with_rng(4294967291 * 4294967279, &mut rng);
+        assert_eq!(fac, BTreeMap::from([(4294967279, 1), (4294967291, 1)]));
+        assert_ne!(rng.gen::<u64>(), StdRng::seed_from_u64(0).gen::<u64>());
+    }
+
+    #[test]
+    fn factorize64_test() {
+        // some simple cases
+        let fac4095 = BTreeMap::from_iter([(3, 2), (5, 1), (7, 1), (13, 1)]);
         let fac = factorize64(4095);
         assert_eq!(fac, fac4095);
 
-        let fac123456789 = BTreeMap::from_iter([(3, 2), (3803, 1), (3607, 1)]);
-        let fac = factorize64(123456789);
-        assert_eq!(fac, fac123456789);
+        let fac123456789 = BTreeMap::from_iter([(3, 2), (3803, 1), (3607, 1)]);
+        let fac = factorize64(123456789);
+        assert_eq!(fac, fac123456789);
+
+        let fac1_17 = BTreeMap::from_iter([(2071723, 1), (5363222357, 1)]);
+        let fac = factorize64(11111111111111111);
+        assert_eq!(fac, fac1_17);
+
+        // perfect powers
+        let fac = factorize64(104729 * 104729);
+        assert_eq!(fac, BTreeMap::from_iter([(104729, 2)]));
+        for exp in 2u32..5 {
+            assert_eq!(
+                factorize128(8167u128.pow(exp)),
+                BTreeMap::from_iter([(8167, exp as usize)])
+            );
+        }
+
+        // 100 random factorization tests
+        for _ in 0..100 {
+            let x = random();
+            let fac = factorize64(x);
+            let mut prod = 1;
+            for (p, exp) in fac {
+                assert!(
+                    is_prime64(p),
+                    "factorization result should have prime factors! (get {})",
+                    p
+                );
+                prod *= p.pow(exp as u32);
+            }
+            assert_eq!(x, prod, "factorization check failed! ({} != {})", x, prod);
+        }
+    }
+
+    #[test]
+    fn factorize128_test() {
+        // some simple cases
+        let fac_primorial19 =
+            BTreeMap::from_iter(SMALL_PRIMES.iter().take(19).map(|&p| (p as u128, 1)));
+        let fac = factorize128(7858321551080267055879090);
+        assert_eq!(fac, fac_primorial19);
+
+        let fac_smallbig = BTreeMap::from_iter([(167, 1), (2417851639229258349412369, 1)]);
+        let fac = factorize128(403781223751286144351865623);
+        assert_eq!(fac, fac_smallbig);
+
+        // perfect powers
+        for exp in 5u32..10 {
+            // 2^64 < 8167^5 < 8167^9 < 2^128
+            assert_eq!(
+                factorize128(8167u128.pow(exp)),
+                BTreeMap::from_iter([(8167, exp as usize)])
+            );
+        }
+
+        // random factorization tests
+        for _ in 0..4 {
+            let x = random::<u128>() >> 28; // test 100 bit numbers
+            let fac = factorize128(x);
+            let mut prod = 1;
+            for (p, exp) in fac {
+                assert!(
+                    is_prime(&p, None).probably(),
+                    "factorization result should have prime factors! (get {})",
+                    p
+                );
+                prod *= p.pow(exp as u32);
+            }
+            assert_eq!(x, prod, "factorization check failed! ({} != {})", x, prod);
+        }
+    }
+
+    #[test]
+    fn is_safe_prime_test() {
+        // OEIS:A005385
+        let safe_primes = [
+            5u16, 7, 11, 23, 47, 59, 83, 107, 167, 179, 227, 263, 347, 359, 383, 467, 479, 503,
+            563, 587, 719, 839, 863, 887, 983, 1019, 1187, 1283, 1307, 1319, 1367, 1439, 1487,
+            1523, 1619, 1823, 1907,
+        ];
+        for p in SMALL_PRIMES {
+            let p = p as u16;
+            if p > 1500 {
+                break;
+            }
+            assert_eq!(
+                is_safe_prime(&p).probably(),
+                safe_primes.iter().find(|&v| &p == v).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn prime_filter_test() {
+        // primes in the arithmetic progression 4k + 3 (OEIS:A002145)
+        let ap: Vec<u32> = prime_filter((0..20).map(|k| 4 * k + 3), None).collect();
+        assert_eq!(ap, [3, 7, 11, 19, 23, 31, 43, 47, 59, 67, 71, 79]);
+
+        // 10 primes in arithmetic progression starting at 199 with difference 210
+        let ap: Vec<u64> = prime_filter((0..12).map(|k| 199 + 210 * k), None).collect();
+        assert_eq!(ap.len(), 10);
+        assert_eq!(ap.last(), Some(&2089));
+
+        let p: Vec<u64> = prime_filter(0..10000u64, None).collect();
+        assert_eq!(p, primes(10000));
+        let big = ((1u128 << 64) + 1..).step_by(2).take(100);
+        let count = prime_filter(big, Some(PrimalityTestConfig::bpsw())).count();
+        assert!(count > 0 && count < 100);
+    }
+
+    #[test]
+    fn small_primes_test() {
+        let table = small_primes();
+        assert_eq!(table.len(), small_primes_count());
+        assert_eq!(&table[..4], &[2, 3, 5, 7]);
+        assert!(table.windows(2).all(|w| w[0] < w[1]));
+        assert!(table.iter().all(|&p| is_prime64(p as u64)));
+        let last = *table.last().unwrap() as u64;
+        assert_eq!(next_prime(&last, None), Some(SMALL_PRIMES_NEXT));
+        let expected: Vec<u64> = table.iter().map(|&p| p as u64).collect();
+        assert_eq!(primes(last), expected);
+    }
+
+    #[test]
+    fn prime_cache_test() {
+        let p = primes(100000);
+        assert_eq!(p.len(), 9592);
+        assert!(PRIME_CACHE.with(|pb| pb.borrow().bound()) >= 99991);
+
+        // repeated calls reuse the buffer and return consistent results
+        for _ in 0..3 {
+            assert_eq!(primes(100000), p);
+            assert_eq!(primes(1000), p[..168]);
+            assert_eq!(nprimes(9592), p);
+            assert_eq!(prime_pi(100000), 9592);
+            assert_eq!(prime_pi(10000000), 664579);
+            assert_eq!(nth_prime(9592), 99991);
+            assert_eq!(nth_prime(1000000), 15485863);
+        }
+
+        clear_prime_cache();
+        assert_eq!(PRIME_CACHE.with(|pb| pb.borrow().bound()), 53);
+        assert_eq!(primes(100000), p);
+    }
+
+    #[test]
+    fn primorial_checked_test() {
+        assert_eq!(primorial_checked::<u64>(0), Some(1));
+        assert_eq!(primorial_checked::<u64>(15), Some(614889782588491410));
+        assert_eq!(primorial_checked::<u64>(16), None);
+        assert_eq!(primorial_checked::<u128>(16), Some(32589158477190044730));
+        assert_eq!(primorial_checked::<u128>(26), Some(primorial(26)));
+        assert_eq!(primorial_checked::<u128>(27), None);
+        assert_eq!(primorial_checked::<u8>(4), Some(210));
+        assert_eq!(primorial_checked::<u8>(5), None);
+        for n in 0..16 {
+            assert_eq!(primorial_checked::<u64>(n), Some(primorial(n)));
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p: BigUint = primorial_checked(100).unwrap();
+            assert_eq!(p, primorial(100));
+        }
+    }
+
+    #[test]
+    fn factors_with_hint_test() {
+        let target = 1000000007u64 * 998244353 * 12;
+        let expected = factorize64(target);
+        for hint in [1000000007, 998244353 * 4, 6, 2] {
+            assert_eq!(factors_with_hint(target, &hint, None), Ok(expected.clone()));
+        }
+
+        // invalid hints are reported
+        for hint in [1, 0, 5, target, 1000000009] {
+            assert_eq!(factors_with_hint(target, &hint, None), Err(vec![target]));
+        }
+
+        // the hint helps when the factorization is hard
+        #[cfg(feature = "num-bigint")]
+        {
+            let p1 = BigUint::from(2u8).pow(89) - 1u8; // M89
+            let p2 = BigUint::from(2u8).pow(107) - 1u8; // M107
+            let target = &p1 * &p2 * 15u8;
+            let result = factors_with_hint(target.clone(), &p2, None).unwrap();
+            assert_eq!(result.len(), 4);
+            assert_eq!(result[&p1], 1);
+            assert_eq!(result[&p2], 1);
+
+            // failed cofactors are proper divisors, unlike the error of a rejected hint
+            let mut config = FactorizationConfig::default();
+            config.rho_trials = 0;
+            let p3 = BigUint::from(18446744073709551557u64);
+            let target = &p1 * &p2 * &p3;
+            let result = factors_with_hint(target.clone(), &p3, Some(config));
+            assert_eq!(result, Err(vec![&p1 * &p2]));
+            let result = factors_with_hint(target.clone(), &(&p3 + 2u8), Some(config));
+            assert_eq!(result, Err(vec![target]));
+        }
+    }
+
+    #[test]
+    fn is_square_free_test() {
+        for n in 0..20000u64 {
+            assert_eq!(is_square_free(&n), moebius(&n) != 0, "{}", n);
+        }
+
+        // large inputs
+        let (p, q, r) = (1000000007u128, 998244353u128, 18446744073709551557u128);
+        assert!(is_square_free(&(p * q)));
+        assert!(is_square_free(&(p * q * 30)));
+        assert!(is_square_free(&(p * r)));
+        assert!(!is_square_free(&(p * p)));
+        assert!(!is_square_free(&(p * p * q)));
+        assert!(!is_square_free(&(q * r * 49)));
+        assert!(!is_square_free(&(8191 * 8191 * p * 3)));
+
+        // inputs close to the range limit of the integer type
+        assert!(is_square_free(&251u8));
+        assert!(!is_square_free(&250u8));
+        for n in 1..=u16::MAX {
+            assert_eq!(is_square_free(&n), moebius(&(n as u64)) != 0, "{}", n);
+        }
+        assert!(is_square_free(&(8171u32 * 8179)));
+        assert!(!is_square_free(&(8171u32 * 8171)));
+        assert!(is_square_free(&(65521u64 * 65519 * 65497)));
+        for _ in 0..100 {
+            let x = random::<u64>() as u128 * random::<u32>() as u128;
+            assert_eq!(is_square_free(&x), moebius(&x) != 0);
+        }
+    }
+
+    #[test]
+    fn try_moebius_test() {
+        for n in 1..2000u64 {
+            assert_eq!(try_moebius(&n, None), Some(moebius(&n)));
+            assert_eq!(try_is_square_free(&n, None), Some(is_square_free(&n)));
+        }
+        assert_eq!(try_is_square_free(&0u32, None), Some(false));
+
+        #[cfg(feature = "num-bigint")]
+        {
+            // a semiprime that can't be factored without rho trials
+            let p = next_prime(&(BigUint::from(1u8) << 80u32), None).unwrap();
+            let q = next_prime(&(BigUint::from(1u8) << 81u32), None).unwrap();
+            let mut config = FactorizationConfig::default();
+            config.rho_trials = 0;
+
+            let n = &p * &q;
+            assert_eq!(try_moebius(&n, Some(config)), None);
+            assert_eq!(try_is_square_free(&n, Some(config)), None);
+            assert_eq!(try_moebius(&(&n * 2u8), Some(config)), None);
+
+            // decided without the factorization
+            assert_eq!(try_moebius(&(&n * 9u8), Some(config)), Some(0));
+            assert_eq!(try_is_square_free(&(&n * &p), Some(config)), None);
+            assert_eq!(try_is_square_free(&(&n * 49u8), Some(config)), Some(false));
+            assert_eq!(try_moebius(&p, Some(config)), Some(-1));
+            assert_eq!(try_is_square_free(&(&p * &p), Some(config)), Some(false));
+        }
+    }
+
+    #[test]
+    fn moebius_test() {
+        // test small examples
+        let mu20: [i8; 20] = [
+            1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0, -1, 1, 1, 0, -1, 0, -1, 0,
+        ];
+        for i in 0..20 {
+            assert_eq!(moebius(&(i + 1)), mu20[i], "moebius on {}", i);
+        }
+
+        // some square numbers
+        assert_eq!(moebius(&1024u32), 0);
+        assert_eq!(moebius(&(8081u32 * 8081)), 0);
+
+        // sphenic numbers
+        let sphenic3: [u8; 20] = [
+            30, 42, 66, 70, 78, 102, 105, 110, 114, 130, 138, 154, 165, 170, 174, 182, 186, 190,
+            195, 222,
+        ]; // OEIS:A007304
+        for i in 0..20 {
+            assert_eq!(moebius(&sphenic3[i]), -1i8, "moebius on {}", sphenic3[i]);
+        }
+        let sphenic5: [u16; 23] = [
+            2310, 2730, 3570, 3990, 4290, 4830, 5610, 6006, 6090, 6270, 6510, 6630, 7410, 7590,
+            7770, 7854, 8610, 8778, 8970, 9030, 9282, 9570, 9690,
+        ]; // OEIS:A046387
+        for i in 0..20 {
+            assert_eq!(moebius(&sphenic5[i]), -1i8, "moebius on {}", sphenic5[i]);
+        }
+    }
+
+    #[test]
+    fn prime_pi_bounds_test() {
+        fn check(n: u64, pi: u64) {
+            let (lo, hi) = prime_pi_bounds(&n);
+            let est = prime_pi_est(&n);
+            assert!(
+                lo <= pi && pi <= hi,
+                "fail to satisfy {} <= pi({}) = {} <= {}",
+                lo,
+                n,
+                pi,
+                hi
+            );
+            assert!(lo <= est && est <= hi);
+        }
+
+        // test with sieved primes
+        let mut pb = NaiveBuffer::new();
+        let mut last = 0;
+        for (i, p) in pb.primes(100000).cloned().enumerate() {
+            for j in last..p {
+                check(j, i as u64);
+            }
+            last = p;
+        }
+
+        // test with some known cases with input as 10^n, OEIS:A006880
+        let pow10_values = [
+            0,
+            4,
+            25,
+            168,
+            1229,
+            9592,
+            78498,
+            664579,
+            5761455,
+            50847534,
+            455052511,
+            4118054813,
+            37607912018,
+            346065536839,
+            3204941750802,
+            29844570422669,
+            279238341033925,
+            2623557157654233,
+        ];
+        for (exponent, gt) in pow10_values.iter().enumerate() {
+            let n = 10u64.pow(exponent as u32);
+            check(n, *gt);
+        }
+    }
+
+    #[test]
+    fn nth_prime_bounds_test() {
+        fn check(n: u64, p: u64) {
+            let (lo, hi) = super::nth_prime_bounds(&n).unwrap();
+            assert!(
+                lo <= p && p <= hi,
+                "fail to satisfy: {} <= {}-th prime = {} <= {}",
+                lo,
+                n,
+                p,
+                hi
+            );
+            let est = super::nth_prime_est(&n).unwrap();
+            assert!(lo <= est && est <= hi);
+        }
+
+        // test with sieved primes
+        let mut pb = NaiveBuffer::new();
+        for (i, p) in pb.primes(100000).cloned().enumerate() {
+            check(i as u64 + 1, p as u64);
+        }
+
+        // test with some known cases with input as 10^n, OEIS:A006988
+        let pow10_values = [
+            2,
+            29,
+            541,
+            7919,
+            104729,
+            1299709,
+            15485863,
+            179424673,
+            2038074743,
+            22801763489,
+            252097800623,
+            2760727302517,
+            29996224275833,
+            323780508946331,
+            3475385758524527,
+            37124508045065437,
+        ];
+        for (exponent, nth_prime) in pow10_values.iter().enumerate() {
+            let n = 10u64.pow(exponent as u32);
+            check(n, *nth_prime);
+        }
+    }
+
+    #[test]
+    fn prev_next_test() {
+        assert_eq!(prev_prime(&2u32, None), None);
+
+        // prime table boundary test
+        assert_eq!(prev_prime(&257u16, None), Some(251));
+        assert_eq!(next_prime(&251u16, None), Some(257));
+        assert_eq!(next_prime(&251u8, None), None);
+        assert_eq!(next_prime(&252u8, None), None);
+        assert_eq!(next_prime(&255u8, None), None);
+        assert_eq!(prev_prime(&8167u16, None), Some(8161));
+        assert_eq!(next_prime(&8161u16, None), Some(8167));
+
+        // OEIS:A077800
+        let twine_primes: [(u32, u32); 8] = [
+            (2, 3), // not exactly twine
+            (3, 5),
+            (5, 7),
+            (11, 13),
+            (17, 19),
+            (29, 31),
+            (41, 43),
+            (617, 619),
+        ];
+        for (p1, p2) in twine_primes {
+            assert_eq!(prev_prime(&p2, None).unwrap(), p1);
+            assert_eq!(next_prime(&p1, None).unwrap(), p2);
+        }
+
+        let adj10_primes: [(u32, u32); 7] = [
+            (7, 11),
+            (97, 101),
+            (997, 1009),
+            (9973, 10007),
+            (99991, 100003),
+            (999983, 1000003),
+            (9999991, 10000019),
+        ];
+        for (i, (p1, p2)) in adj10_primes.iter().enumerate() {
+            assert_eq!(prev_prime(p2, None).unwrap(), *p1);
+            assert_eq!(next_prime(p1, None).unwrap(), *p2);
+
+            let pow = 10u32.pow((i + 1) as u32);
+            assert_eq!(prev_prime(&pow, None).unwrap(), *p1);
+            assert_eq!(next_prime(&pow, None).unwrap(), *p2);
+        }
+    }
+
+    #[test]
+    fn omega_sum_test() {
+        let (mut omega, mut big_omega) = (0, 0);
+        for k in 1..2000u64 {
+            let f = factorize64(k);
+            omega += f.len() as u64;
+            big_omega += f.values().sum::<usize>() as u64;
+            assert_eq!(omega_sum(k), omega);
+            assert_eq!(big_omega_sum(k), big_omega);
+        }
+        assert_eq!(omega_sum(0), 0);
+        assert_eq!(big_omega_sum(0), 0);
+
+        // OEIS A013939 and A022559
+        assert_eq!(omega_sum(100), 171);
+        assert_eq!(big_omega_sum(100), 239);
+    }
+
+    #[test]
+    fn bpsw_pseudoprimes_test() {
+        assert!(bpsw_pseudoprimes_below(0).is_empty());
+        assert!(bpsw_pseudoprimes_below(1 << 20).is_empty());
+    }
+
+    #[test]
+    #[ignore = "takes a long time, run with --ignored"]
+    fn bpsw_pseudoprimes_large_test() {
+        assert!(bpsw_pseudoprimes_below(1 << 32).is_empty());
+    }
+
+    #[test]
+    fn powm_signed_test() {
+        let p = 1000000007u64;
+        for _ in 0..10 {
+            let a = random::<u64>() % (p - 1) + 1;
+            let inv = a.invm(&p).unwrap();
+            assert_eq!(powm_signed(&a, -1, &p), Some(inv));
+            assert_eq!(powm_signed(&a, -2, &p), Some(inv.mulm(inv, &p)));
+            assert_eq!(powm_signed(&a, 3, &p), Some(a.powm(3, &p)));
+            assert_eq!(powm_signed(&a, 0, &p), Some(1));
+            let e = random::<i64>();
+            let x = powm_signed(&a, e, &p).unwrap();
+            let y = powm_signed(&a, -e, &p).unwrap();
+            assert_eq!(x.mulm(y, &p), 1);
+        }
+
+        assert_eq!(powm_signed(&2u32, -1, &7), Some(4));
+        assert_eq!(powm_signed(&2u32, -3, &7), Some(1));
+        assert_eq!(powm_signed(&2u32, -1, &8), None);
+        assert_eq!(powm_signed(&2u32, 3, &8), Some(0));
+        assert_eq!(powm_signed(&9u32, -1, &7), Some(4));
+
+        // exponents larger than the integer type
+        let expected = 3u64.invm(&251).unwrap().powm(1 << 63, &251) as u8;
+        assert_eq!(powm_signed(&3u8, i64::MIN, &251), Some(expected));
+        let expected = 3u64.powm(1000, &251) as u8;
+        assert_eq!(powm_signed(&3u8, 1000, &251), Some(expected));
+    }
+
+    #[test]
+    fn digit_sum_test() {
+        assert_eq!(digit_sum(12345, 10), 15);
+        assert_eq!(digital_root(12345, 10), 6);
+        assert_eq!(digit_sum(0, 10), 0);
+        assert_eq!(digital_root(0, 10), 0);
+        assert_eq!(digital_root(9, 10), 9);
+        assert_eq!(digital_root(18, 10), 9);
+        assert_eq!(digit_sum(0xff, 16), 30);
+        assert_eq!(digital_root(0xff, 16), 15);
+        assert_eq!(digit_sum(255, 2), 8);
+        assert_eq!(digital_root(255, 2), 1);
+        assert_eq!(digit_sum(u64::MAX, 10), 87);
+
+        // compare with the definition
+        for _ in 0..100 {
+            let n = random::<u64>();
+            let base = random::<u64>() % 35 + 2;
+            let mut r = n;
+            while r >= base {
+                r = digit_sum(r, base);
+            }
+            assert_eq!(digital_root(n, base), r);
+        }
+    }
+
+    #[test]
+    fn carmichael_test() {
+        // OEIS A002997
+        let carmichaels: [u64; 16] = [
+            561, 1105, 1729, 2465, 2821, 6601, 8911, 10585, 15841, 29341, 41041, 46657, 52633,
+            62745, 63973, 75361,
+        ];
+        let found: Vec<u64> = (1..80000).filter(|&n| is_carmichael(n)).collect();
+        assert_eq!(found, carmichaels);
+
+        // the direct Fermat check agrees with Korselt's criterion
+        for n in 0..20000 {
+            assert_eq!(is_absolute_fermat_pseudoprime(n), is_carmichael(n), "{}", n);
+        }
+        for n in [75361, 101101, 252601, 9999109081, 3778118040573702001] {
+            assert!(is_absolute_fermat_pseudoprime(n));
+        }
+        for n in [75363, 1000000007, 2047, 25326001] {
+            // 2047 and 25326001 are strong pseudoprimes, but not Carmichael numbers
+            assert!(!is_absolute_fermat_pseudoprime(n));
+        }
+    }
+
+    #[test]
+    fn rational_reconstruct_test() {
+        let p = 1000000007u64;
+        let b = 22360; // floor(sqrt(p / 2))
+        let frac = |n: u64, d: u64| n * d.invm(&p).unwrap() % p;
+        assert_eq!(rational_reconstruct(&frac(1, 3), &p, &b, &b), Some((1, 3)));
+        assert_eq!(rational_reconstruct(&frac(2, 5), &p, &b, &b), Some((2, 5)));
+        let r = frac(123, 4567);
+        assert_eq!(rational_reconstruct(&r, &p, &b, &b), Some((123, 4567)));
+        assert_eq!(rational_reconstruct(&b, &p, &b, &b), Some((b, 1)));
+        assert_eq!(rational_reconstruct(&0, &p, &b, &b), Some((0, 1)));
+
+        // negative fractions
+        let r = frac(3, 7);
+        assert_eq!(rational_reconstruct(&(p - r), &p, &b, &b), None);
+        assert_eq!(rational_reconstruct(&r, &p, &b, &b), Some((3, 7)));
+
+        // out of bounds
+        assert_eq!(rational_reconstruct(&frac(1, 3), &p, &b, &2), None);
+        assert_eq!(rational_reconstruct(&frac(30000, 7), &p, &b, &b), None);
+
+        let p = (1u128 << 127) - 1;
+        let b = 1u128 << 60;
+        let r = 987654321u128.mulm(123456789u128.invm(&p).unwrap(), &p);
+        let expected = (987654321 / 9, 123456789 / 9); // gcd is 9
+        assert_eq!(rational_reconstruct(&r, &p, &b, &b), Some(expected));
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p = BigUint::from(p);
+            let b = BigUint::from(b);
+            let r = BigUint::from(r);
+            let expected = (BigUint::from(expected.0), BigUint::from(expected.1));
+            assert_eq!(rational_reconstruct(&r, &p, &b, &b), Some(expected));
+        }
+    }
+
+    #[test]
+    fn squfof_full_test() {
+        let multipliers: Vec<u64> = SQUFOF_MULTIPLIERS.iter().map(|&k| k as u64).collect();
+        let cases: [u64; 6] = [
+            11111,
+            1000000007 * 998244353,
+            4294967291 * 65521,
+            2147483647 * 2147483629,
+            104729 * 104729,
+            3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 31,
+        ];
+        for n in cases {
+            let p = squfof_full(&n, &multipliers, 1 << 14).unwrap();
+            assert!(p > 1 && p < n && n % p == 0, "{} / {}", n, p);
+            let fac = factorize64(n);
+            assert!(factorize64(p).iter().all(|(q, e)| fac[q] >= *e));
+        }
+        for _ in 0..10 {
+            let p1 = next_prime(&(random::<u32>() as u64 >> 4), None).unwrap();
+            let p2 = next_prime(&(random::<u32>() as u64 >> 4), None).unwrap();
+            let n = p1 * p2;
+            let p = squfof_full(&n, &multipliers, 1 << 14).unwrap();
+            assert!(p > 1 && p < n && n % p == 0, "{} / {}", n, p);
+        }
+
+        // primes have no nontrivial factor
+        assert_eq!(squfof_full(&1000000007u64, &multipliers, 1 << 10), None);
+        // overflowing multipliers are skipped
+        assert_eq!(squfof_full(&u64::MAX, &[u64::MAX], 1 << 10), None);
+        let n = 1000000007u64 * 998244353;
+        let mut skipped = vec![u64::MAX, u64::MAX >> 4];
+        skipped.extend_from_slice(&multipliers);
+        assert_eq!(
+            squfof_full(&n, &skipped, 1 << 14),
+            squfof_full(&n, &multipliers, 1 << 14)
+        );
+
+        // small integer types
+        for n in [251u16 * 241, 63001, 3 * 21841, 65535] {
+            let p = squfof_full(&n, &multipliers, 1 << 10).unwrap();
+            assert!(p > 1 && p < n && n % p == 0, "{} / {}", n, p);
+        }
+        assert_eq!(squfof_full(&65521u16, &multipliers, 1 << 10), None);
+        #[cfg(feature = "num-bigint")]
+        {
+            let n = BigUint::from(1000003u64 * 998244353);
+            let p = squfof_full(&n, &multipliers, 1 << 16).unwrap();
+            assert!(p > BigUint::from(1u8) && p < n);
+            assert_eq!(&n % &p, BigUint::from(0u8));
+        }
+    }
+
+    #[test]
+    fn generic_modular_test() {
+        use core::ops::{Add, Mul};
+        use num_traits::{One, Zero};
+
+        // a newtype that only implements the adapter
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct W(u128);
+        impl Add for W {
+            type Output = W;
+            fn add(self, rhs: W) -> W {
+                W(self.0 + rhs.0)
+            }
+        }
+        impl Mul for W {
+            type Output = W;
+            fn mul(self, rhs: W) -> W {
+                W(self.0 * rhs.0)
+            }
+        }
+        impl Zero for W {
+            fn zero() -> W {
+                W(0)
+            }
+            fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+        }
+        impl One for W {
+            fn one() -> W {
+                W(1)
+            }
+        }
+        impl ModularPrimitives for W {
+            fn mulm(&self, rhs: &W, m: &W) -> W {
+                W(ModularCoreOps::mulm(self.0, rhs.0, &m.0))
+            }
+            fn subm(&self, rhs: &W, m: &W) -> W {
+                W(ModularCoreOps::subm(self.0, rhs.0, &m.0))
+            }
+            fn div_rem(&self, rhs: &W) -> (W, W) {
+                (W(self.0 / rhs.0), W(self.0 % rhs.0))
+            }
+        }
+
+        let m = (1u128 << 127) - 1; // Mersenne prime
+        for _ in 0..20 {
+            let (a, e, n) = (random::<u128>(), random::<u128>(), random::<u128>() | 1);
+            let r = generic_powm(&W(a), &W(e), &W(n));
+            assert_eq!(r.0, a.powm(e, &n));
+            let r = generic_invm(&W(a), &W(n));
+            assert_eq!(r.map(|v| v.0), a.invm(&n));
+            assert_eq!(generic_jacobi(&W(a), &W(n)), a.jacobi(&n));
+
+            let r = generic_invm(&W(a % m), &W(m)).unwrap();
+            assert_eq!(ModularCoreOps::mulm(r.0, a, &m), 1);
+        }
+
+        assert_eq!(generic_powm(&W(3), &W(0), &W(1)), W(0));
+        assert_eq!(generic_powm(&W(2), &W(10), &W(1000)), W(24));
+        assert_eq!(generic_invm(&W(3), &W(7)), Some(W(5)));
+        assert_eq!(generic_invm(&W(4), &W(8)), None);
+        assert_eq!(generic_jacobi(&W(2), &W(7)), 1);
+        assert_eq!(generic_jacobi(&W(5), &W(21)), 1);
+        assert_eq!(generic_jacobi(&W(6), &W(21)), 0);
+    }
+
+    #[test]
+    fn smarandache_test() {
+        // https://oeis.org/A002034
+        let s: Vec<u64> = (1..25).map(|n| smarandache(&n)).collect();
+        let expected = [
+            1, 2, 3, 4, 5, 3, 7, 4, 6, 5, 11, 4, 13, 7, 5, 6, 17, 6, 19, 5, 7, 11, 23, 4,
+        ];
+        assert_eq!(s, expected);
+        for p in [97u64, 65537, 1000000007] {
+            assert_eq!(smarandache(&p), p);
+        }
+        assert_eq!(smarandache(&(1u64 << 20)), 24);
+    }
+
+    #[test]
+    fn is_practical_test() {
+        // https://oeis.org/A005153
+        let practical: Vec<u64> = (0..100u64).filter(|n| is_practical(n)).collect();
+        let expected = [
+            1, 2, 4, 6, 8, 12, 16, 18, 20, 24, 28, 30, 32, 36, 40, 42, 48, 54, 56, 60, 64, 66, 72,
+            78, 80, 84, 88, 90, 96,
+        ];
+        assert_eq!(practical, expected);
+        assert!(is_practical(&(1u64 << 40)));
+        assert!(!is_practical(&(2u64 * 1000000007)));
+    }
+
+    #[test]
+    fn exponent_vector_test() {
+        let base = [2u64, 3, 5, 7, 11];
+        assert_eq!(exponent_vector(&1u64, &base), Some(vec![0, 0, 0, 0, 0]));
+        assert_eq!(exponent_vector(&792, &base), Some(vec![3, 2, 0, 0, 1]));
+        assert_eq!(exponent_vector(&350, &base), Some(vec![1, 0, 2, 1, 0]));
+        assert_eq!(exponent_vector(&(4 * 13), &base), None);
+        assert_eq!(exponent_vector(&0u64, &base), None);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let base: Vec<BigUint> = [2u32, 3, 65537].iter().map(|&p| BigUint::from(p)).collect();
+            let n = BigUint::from(65537u32).pow(5u32) * BigUint::from(6u32);
+            assert_eq!(exponent_vector(&n, &base), Some(vec![1, 1, 5]));
+        }
+    }
+
+    #[test]
+    fn cfrac_test() {
+        let cases: [u64; 4] = [
+            1000000007 * 998244353,
+            4294967291 * 65521,
+            2147483647 * 2147483629,
+            3 * 5 * 7 * 11 * 13,
+        ];
+        for n in cases {
+            let p = cfrac(&n, 200).unwrap();
+            assert!(p > 1 && p < n && n % p == 0, "{} / {}", n, p);
+        }
+        assert_eq!(cfrac(&(104729u64 * 104729), 10), Some(104729));
+        assert_eq!(cfrac(&1000000007u64, 100), None);
+        let p = cfrac(&(251u16 * 233), 50).unwrap();
+        assert!(p == 251 || p == 233);
+
+        // 2^67 - 1 = 193707721 * 761838257287
+        #[cfg(feature = "num-bigint")]
+        {
+            let n = (BigUint::from(1u8) << 67u8) - BigUint::from(1u8);
+            let p = cfrac(&n, 300).unwrap();
+            assert!(p == BigUint::from(193707721u32) || p == BigUint::from(761838257287u64));
+        }
+    }
+
+    #[test]
+    fn primitive_root_of_unity_test() {
+        let p = 998244353; // 119 * 2^23 + 1
+        for order in [1u64, 2, 7, 17, 119, 1 << 10, 1 << 23, 7 << 20, p - 1] {
+            let w = primitive_root_of_unity(p, order).unwrap();
+            assert_eq!(w.powm(order, &p), 1);
+            for (q, _) in factorize64(order) {
+                assert_ne!(w.powm(order / q, &p), 1);
+            }
+        }
+        assert_eq!(primitive_root_of_unity(p, 3), None);
+        assert_eq!(primitive_root_of_unity(p, 0), None);
+        assert_eq!(primitive_root_of_unity(15, 2), None);
+        assert_eq!(primitive_root_of_unity(5, 4), Some(2));
+    }
+
+    #[test]
+    fn divisors_desc_test() {
+        let n = 720720u64; // highly composite, 240 divisors
+        let divs: Vec<u64> = divisors_desc(&n).collect();
+        let mut expected: Vec<u64> = (1..=n).filter(|d| n % d == 0).collect();
+        expected.reverse();
+        assert_eq!(divs, expected);
+
+        assert_eq!(divisors_desc(&1u64).collect::<Vec<_>>(), [1]);
+        assert_eq!(divisors_desc(&97u64).collect::<Vec<_>>(), [97, 1]);
+        assert_eq!(divisors_desc(&(1u64 << 10)).count(), 11);
+
+        // the largest odd divisor
+        let odd = divisors_desc(&(3u64 * 5 * 5 * 64)).find(|d| d % 2 == 1);
+        assert_eq!(odd, Some(75));
+    }
+
+    #[test]
+    fn highly_composite_test() {
+        // https://oeis.org/A002182
+        let expected = [
+            1, 2, 4, 6, 12, 24, 36, 48, 60, 120, 180, 240, 360, 720, 840, 1260, 1680, 2520, 5040,
+            7560, 10080, 15120, 20160, 25200, 27720, 45360, 50400, 55440, 83160, 110880, 166320,
+            221760, 277200, 332640, 498960, 554400, 665280, 720720,
+        ];
+        assert_eq!(highly_composite(720720), expected);
+        assert_eq!(highly_composite(0), []);
+
+        // compare against brute force
+        let mut record = 0;
+        let brute: Vec<u64> = (1..5000u64)
+            .filter(|&n| {
+                let tau = (1..=n).filter(|d| n % d == 0).count();
+                let is_record = tau > record;
+                record = record.max(tau);
+                is_record
+            })
+            .collect();
+        assert_eq!(highly_composite(4999), brute);
+
+        let large = highly_composite(u64::MAX);
+        assert_eq!(large.last(), Some(&18401055938125660800));
+    }
+
+    #[test]
+    fn verify_witness_set_test() {
+        // https://oeis.org/A014233
+        assert_eq!(verify_witness_set(&[2], 10000), Some(2047));
+        assert_eq!(verify_witness_set(&[2], 2047), None);
+        assert_eq!(verify_witness_set(&[2, 3], 1400000), Some(1373653));
+        assert_eq!(verify_witness_set(&[2, 3, 5], 1400000), None);
+        assert_eq!(verify_witness_set(&[2, 7, 61], 1000000), None);
+        assert_eq!(verify_witness_set(&[], 100), Some(9));
+        assert_eq!(verify_witness_set(&[2], 0), None);
+
+        // bases that are multiples of n are skipped, as in is_prime64
+        assert_eq!(verify_witness_set(&[325, 9375, 28178], 1000), None);
+    }
+
+    #[test]
+    fn minimal_witness_set_test() {
+        assert!(minimal_witness_set(0).is_empty());
+        assert!(minimal_witness_set(1).is_empty());
+        assert_eq!(minimal_witness_set(2), [2]);
+        assert_eq!(minimal_witness_set(4), [2]);
+        assert_eq!(minimal_witness_set(2039), [2]);
+        assert_eq!(minimal_witness_set(1000003), [2, 3]);
+        assert_eq!(minimal_witness_set(18446744073709551557).len(), 12);
+
+        // strong pseudoprimes to the first few prime bases, https://oeis.org/A014233
+        assert_eq!(minimal_witness_set(2047), [3]);
+        assert_eq!(minimal_witness_set(3215031751), [11]);
+        assert_eq!(minimal_witness_set(341550071728321), [23]);
+        assert_eq!(minimal_witness_set(3825123056546413051), [37]);
+
+        let passes = |n: u64, b: u64| b % n == 0 || n.is_sprp(b % n);
+        for n in (2..5000u64).chain([1u64 << 32, (1u64 << 61) - 1, u64::MAX]) {
+            let bases = minimal_witness_set(n);
+            if is_prime64(n) {
+                assert!(bases.iter().all(|&b| passes(n, b)), "{}", n);
+            } else {
+                assert_eq!(bases.len(), 1, "{}", n);
+                assert!(!passes(n, bases[0]), "{}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn is_prime_aks_test() {
+        for n in 0..128u64 {
+            assert_eq!(is_prime_aks(&n), is_prime64(n), "{}", n);
+        }
+        // composites without small factors, and Carmichael numbers
+        for n in [561u64, 1105, 1729, 10403, 1022117, 4295098369] {
+            assert!(!is_prime_aks(&n), "{}", n);
+        }
+        assert!(is_prime_aks(&1009u32));
+        for n in 0..=u8::MAX {
+            assert_eq!(is_prime_aks(&n), is_prime64(n as u64), "{}", n);
+        }
+    }
+
+    #[test]
+    fn chebyshev_test() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert_eq!(chebyshev_theta(1), 0.);
+        assert_eq!(chebyshev_psi(1), 0.);
+        assert!(close(chebyshev_theta(10), (210f64).ln()));
+        // ψ(n) = ln(lcm(1, 2, ..., n))
+        assert!(close(chebyshev_psi(10), (2520f64).ln()));
+        assert!(close(chebyshev_psi(16), (720720f64).ln()));
+
+        // ψ(x) / x and θ(x) / x approach 1
+        let mut last_err = f64::MAX;
+        for x in [1000u64, 10000, 1000000] {
+            let (psi, theta) = (chebyshev_psi(x), chebyshev_theta(x));
+            assert!(theta <= psi && psi < 1.04 * x as f64);
+            let err = (psi / x as f64 - 1.).abs();
+            assert!(err < last_err);
+            last_err = err;
+        }
+        assert!(last_err < 1e-3);
+    }
+
+    #[test]
+    fn prime_zeta_test() {
+        assert_eq!(prime_zeta(2., 1), 0.);
+        assert_eq!(prime_zeta(1., 5), 1. / 2. + 1. / 3. + 1. / 5.);
+
+        // P(2) = 0.4522474200..., P(3) = 0.1747626392...
+        let x = 1000000u64;
+        let tail = |s: f64| (x as f64).powf(1. - s) / ((s - 1.) * (x as f64).ln());
+        let p2 = prime_zeta(2., x);
+        assert!(p2 < 0.4522474200 && 0.4522474200 - p2 < 2. * tail(2.));
+        let p3 = prime_zeta(3., x);
+        assert!((p3 - 0.1747626392).abs() < 1e-10);
+    }
+
+    #[test]
+    fn maximal_prime_gaps_test() {
+        assert!(maximal_prime_gaps(2).is_empty());
+        assert_eq!(maximal_prime_gaps(3), [(1, 2)]);
+        assert_eq!(maximal_prime_gaps(10), [(1, 2), (2, 3)]);
+        assert_eq!(maximal_prime_gaps(11), [(1, 2), (2, 3), (4, 7)]);
+        assert_eq!(maximal_prime_gaps(1358), maximal_prime_gaps(1360));
+        assert_eq!(maximal_prime_gaps(1361).last(), Some(&(34, 1327)));
+
+        // A005250 and A002386
+        let records = maximal_prime_gaps(100000);
+        let gaps: Vec<u64> = records.iter().map(|&(g, _)| g).collect();
+        let starts: Vec<u64> = records.iter().map(|&(_, p)| p).collect();
+        assert_eq!(gaps, [1, 2, 4, 6, 8, 14, 18, 20, 22, 34, 36, 44, 52, 72]);
+        assert_eq!(
+            starts,
+            [2, 3, 7, 23, 89, 113, 523, 887, 1129, 1327, 9551, 15683, 19609, 31397]
+        );
+    }
+
+    #[test]
+    fn jordan_totient_sum_test() {
+        for limit in [0u64, 1, 2, 10, 100, 12345] {
+            assert_eq!(jordan_totient_sum(1, limit), totient_sum(limit));
+        }
+
+        // J_2(n) = n^2 * prod (1 - 1/p^2), https://oeis.org/A007434
+        let j2 = [1u128, 3, 8, 12, 24, 24, 48, 48, 72, 72];
+        for (i, _) in j2.iter().enumerate() {
+            let expected: u128 = j2[..=i].iter().sum();
+            assert_eq!(jordan_totient_sum(2, i as u64 + 1), expected);
+        }
+        // J_0(n) is 1 only for n = 1
+        assert_eq!(jordan_totient_sum(0, 1000), 1);
+
+        // compare with the definition
+        let j3 = |n: u64| -> u128 {
+            factorize64(n).into_iter().fold(1, |acc, (p, e)| {
+                let (p, pk) = (p as u128, (p as u128).pow(3 * (e as u32 - 1)));
+                acc * pk * (p * p * p - 1)
+            })
+        };
+        let expected: u128 = (1..=500).map(j3).sum();
+        assert_eq!(jordan_totient_sum(3, 500), expected);
+    }
+
+    #[test]
+    fn coprime_basis_test() {
+        assert_eq!(coprime_basis(&[6u64, 10, 15]), [2, 3, 5]);
+        assert_eq!(coprime_basis(&[12u64, 18]), [2, 3]);
+        assert_eq!(coprime_basis(&[35u64, 77, 0, 1]), [5, 7, 11]);
+        let semiprime = 1000000007u64 * 998244353;
+        assert_eq!(coprime_basis(&[semiprime]), [semiprime]);
+        assert!(coprime_basis::<u64>(&[]).is_empty());
+
+        let values = [360u64, 294, 1 << 20, 1001 * 17, 65536 * 3, 999999999989];
+        let basis = coprime_basis(&values);
+        for (i, a) in basis.iter().enumerate() {
+            assert!(*a > 1);
+            assert!(basis[i + 1..].iter().all(|b| num_integer::gcd(*a, *b) == 1));
+        }
+        // every input is a product of powers of the basis elements
+        for &v in &values {
+            let mut rest = v;
+            for b in &basis {
+                while rest % b == 0 {
+                    rest /= b;
+                }
+            }
+            assert_eq!(rest, 1, "{}", v);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn kronecker_test() {
+        let k = |a: i64, n: i64| kronecker(&BigInt::from(a), &BigInt::from(n));
+
+        // the special moduli
+        assert_eq!((k(1, 0), k(-1, 0), k(2, 0), k(0, 0)), (1, 1, 0, 0));
+        assert_eq!((k(5, -1), k(-5, -1), k(0, -1)), (1, -1, 1));
+        assert_eq!((k(7, 2), k(-7, 2), k(4, 2)), (1, 1, 0));
+        assert_eq!((k(3, 2), k(-3, 2), k(5, -2)), (-1, -1, -1));
+
+        // (-23/p) for the discriminant -23
+        let expected = [1, 1, -1, -1, -1, 1, -1, -1, 0, 1];
+        let primes = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29];
+        for (p, e) in primes.iter().zip(expected) {
+            assert_eq!(k(-23, *p), e, "{}", p);
+        }
+        // (-4/n) is the nontrivial character modulo 4
+        for n in 1..20 {
+            let expected = [0, 1, 0, -1][n as usize % 4];
+            assert_eq!(k(-4, n), expected);
+        }
+
+        // multiplicativity in both arguments with negative values
+        assert_eq!(k(-5, -3), k(-5, -1) * k(-5, 3));
+        for _ in 0..100 {
+            let (a, n) = (random::<i16>() as i64, random::<i16>() as i64);
+            let m = random::<i8>() as i64;
+            assert_eq!(k(a, n * m), k(a, n) * k(a, m), "{} {} {}", a, n, m);
+        }
+
+        // agree with the primitive implementation except for (-1/0)
+        for _ in 0..100 {
+            let (a, n) = (random::<i32>() as i64, random::<i32>() as i64);
+            assert_eq!(k(a, n), a.kronecker(&n), "{} {}", a, n);
+        }
+
+        // 2^127 - 1 is a prime congruent to 3 mod 4, so (-1/p) = -1
+        let p = (BigInt::from(1u8) << 127u8) - 1u8;
+        let a = BigInt::from(12345678901234567890u64);
+        assert_eq!(kronecker(&-a.clone(), &p), -kronecker(&a, &p));
+        assert_eq!(kronecker(&(&a * &a), &p), 1);
+        assert_eq!(kronecker(&(&a * &p), &-p), 0);
+    }
+
+    #[test]
+    fn class_number_naive_test() {
+        // the fundamental discriminants with class number one
+        for d in [-3, -4, -7, -8, -11, -19, -43, -67, -163] {
+            assert_eq!(class_number_naive(d), Some(1), "{}", d);
+        }
+        assert_eq!(class_number_naive(-23), Some(3));
+        assert_eq!(class_number_naive(-20), Some(2));
+        assert_eq!(class_number_naive(-47), Some(5));
+        assert_eq!(class_number_naive(-71), Some(7));
+        assert_eq!(class_number_naive(-12), Some(1)); // non-maximal order
+
+        // x^2 + ny^2 is the only reduced form of discriminant -4n for n = 1, 2, 3, 4, 7
+        let h: Vec<_> = (1..=10).map(|n| class_number_naive(-4 * n)).collect();
+        assert_eq!(h, [1, 1, 1, 1, 2, 2, 1, 2, 2, 2].map(Some));
+
+        assert_eq!(class_number_naive(0), None);
+        assert_eq!(class_number_naive(5), None);
+        assert_eq!(class_number_naive(-5), None);
+        assert_eq!(class_number_naive(-6), None);
+    }
+
+    #[test]
+    fn factorial_prime_exponent_test() {
+        assert_eq!(factorial_prime_exponent(2, 10), 8);
+        assert_eq!(factorial_prime_exponent(5, 100), 24);
+        assert_eq!(factorial_prime_exponent(7, 0), 0);
+        assert_eq!(factorial_prime_exponent(7, 6), 0);
+        assert_eq!(factorial_prime_exponent(2, u64::MAX), u64::MAX - 64);
+
+        // compare with the exponents accumulated from each factor
+        for &p in [2u64, 3, 5, 7, 11].iter() {
+            let mut count = 0;
+            for n in 1..300u64 {
+                let mut m = n;
+                while m % p == 0 {
+                    m /= p;
+                    count += 1;
+                }
+                assert_eq!(factorial_prime_exponent(p, n), count);
+            }
+        }
+    }
+
+    #[test]
+    fn factorial_factorization_test() {
+        assert!(factorial_factorization(0).is_empty());
+        assert!(factorial_factorization(1).is_empty());
+        let f10 = factorial_factorization(10);
+        assert_eq!(f10, BTreeMap::from([(2, 8), (3, 4), (5, 2), (7, 1)]));
+
+        assert!(binomial_factorization(10, 0).is_empty());
+        assert!(binomial_factorization(10, 10).is_empty());
+        let c10_5 = binomial_factorization(10, 5);
+        assert_eq!(c10_5, BTreeMap::from([(2, 2), (3, 2), (7, 1)])); // 252
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let product = |f: BTreeMap<u64, usize>| -> BigUint {
+                f.into_iter()
+                    .map(|(p, e)| BigUint::from(p).pow(e as u32))
+                    .product()
+            };
+            let mut fac = vec![BigUint::from(1u8)];
+            for n in 1..=60u64 {
+                let next = fac.last().unwrap() * n;
+                fac.push(next);
+            }
+            for n in 0..=60u64 {
+                assert_eq!(product(factorial_factorization(n)), fac[n as usize]);
+                for k in 0..=n {
+                    let expected = &fac[n as usize] / &fac[k as usize] / &fac[(n - k) as usize];
+                    assert_eq!(product(binomial_factorization(n, k)), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn factorization_string_test() {
+        assert_eq!(factorization_string(&0u32), "0");
+        assert_eq!(factorization_string(&1u32), "1");
+        assert_eq!(factorization_string(&2u8), "2");
+        assert_eq!(factorization_string(&84u32), "2^2 * 3 * 7");
+        assert_eq!(factorization_string(&1024u64), "2^10");
+        assert_eq!(factorization_string(&360u16), "2^3 * 3^2 * 5");
+        let expected = "3 * 5 * 17 * 257 * 641 * 65537 * 6700417";
+        assert_eq!(factorization_string(&u64::MAX), expected);
+
+        let p = (1u128 << 89) - 1;
+        assert_eq!(factorization_string(&p), p.to_string());
+        assert_eq!(factorization_string(&(p * 9)), format!("3^2 * {}", p));
+    }
+
+    #[test]
+    fn binary_gcd_test() {
+        assert_eq!(binary_gcd(0u32, 0), 0);
+        assert_eq!(binary_gcd(0u32, 12), 12);
+        assert_eq!(binary_gcd(12u32, 0), 12);
+        assert_eq!(binary_gcd(12u32, 18), 6);
+        assert_eq!(binary_gcd(1u8, 255), 1);
+        assert_eq!(binary_gcd(128u8, 192), 64);
+        assert_eq!(binary_gcd(u64::MAX, u64::MAX), u64::MAX);
+
+        for _ in 0..1000 {
+            let (a, b) = (random::<u32>() as u64, random::<u32>() as u64);
+            let c = random::<u16>() as u64;
+            assert_eq!(binary_gcd(a * c, b * c), num_integer::gcd(a * c, b * c));
+            let (a, b) = (random::<u128>(), random::<u128>());
+            assert_eq!(binary_gcd(a, b), num_integer::gcd(a, b));
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p = BigUint::from(1u8) << 200u32;
+            let q = BigUint::from(random::<u128>()) * BigUint::from(random::<u128>());
+            let r = BigUint::from(random::<u64>()) * 6u8;
+            let expected = num_integer::Integer::gcd(&(&p * &r), &(&q * &r));
+            assert_eq!(binary_gcd(&p * &r, &q * &r), expected);
+        }
+    }
+
+    #[test]
+    fn coprime_gap_test() {
+        // https://oeis.org/A048670, j(p#)
+        let primorials = [1u64, 2, 6, 30, 210, 2310, 30030, 510510];
+        let gaps: Vec<u64> = primorials.iter().map(|&m| coprime_gap(m)).collect();
+        assert_eq!(gaps, [1, 2, 4, 6, 10, 14, 22, 26]);
+
+        // compare with the definition
+        for m in 1..200u64 {
+            let expected = (1..=m)
+                .find(|&len| (0..m).all(|s| (s..s + len).any(|k| num_integer::gcd(k, m) == 1)))
+                .unwrap();
+            assert_eq!(coprime_gap(m), expected, "m = {}", m);
+        }
+    }
+
+    #[test]
+    fn wieferich_wilson_prime_test() {
+        let wieferich: Vec<u64> = (0..10000).filter(|&p| is_wieferich_prime(p)).collect();
+        assert_eq!(wieferich, [1093, 3511]);
+        assert!(!is_wieferich_prime(1093 * 3511));
+        assert!(!is_wieferich_prime(18446744073709551557));
+
+        let wilson: Vec<u64> = (0..2000).filter(|&p| is_wilson_prime(p)).collect();
+        assert_eq!(wilson, [5, 13, 563]);
+        assert!(!is_wilson_prime(4));
+    }
+
+    #[test]
+    fn inverse_table_test() {
+        let expected = [None, Some(1), Some(4), Some(5), Some(2), Some(3), Some(6)];
+        assert_eq!(inverse_table(&7u8), expected);
+        let expected = [None, Some(1), None, None, None, Some(5)];
+        assert_eq!(inverse_table(&6u32), expected);
+        assert_eq!(inverse_table(&2u32), [None, Some(1)]);
+
+        for m in (1..300u64).chain([65537, 65536, 99991]) {
+            let table = inverse_table(&m);
+            assert_eq!(table.len(), m as usize);
+            for (i, inv) in table.into_iter().enumerate() {
+                assert_eq!(inv, (i as u64).invm(&m), "{}^-1 mod {}", i, m);
+            }
+        }
+    }
+
+    #[test]
+    fn prime_bitmap_test() {
+        let bit = |bitmap: &[u64], lo: u64, n: u64| {
+            let i = (n - lo) as usize;
+            bitmap[i / 64] >> (i % 64) & 1 == 1
+        };
+
+        assert!(prime_bitmap(10, 9).is_empty());
+        assert_eq!(prime_bitmap(0, 10), [0b10101100]);
+        assert_eq!(prime_bitmap(0, 63), [0x28208a20a08a28ac]);
+
+        let windows = [(0, 1000), (1000, 1100), (1 << 40, (1 << 40) + 1000)];
+        for &(lo, hi) in windows.iter() {
+            let bitmap = prime_bitmap(lo, hi);
+            assert_eq!(bitmap.len() as u64, (hi - lo) / 64 + 1);
+            for n in lo..=hi {
+                assert_eq!(bit(&bitmap, lo, n), is_prime64(n), "n = {}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn divm_test() {
+        assert_eq!(divm(&1u32, &2, &7), Some(4));
+        assert_eq!(divm(&17u32, &3, &10), Some(9));
+        assert_eq!(divm(&1u32, &4, &10), None);
+        assert_eq!(divm(&0u32, &3, &10), Some(0));
+
+        for _ in 0..100 {
+            let (a, b, m) = (random::<u64>(), random::<u64>(), random::<u64>() | 1);
+            let expected = b.invm(&m).map(|bi| a.mulm(bi, &m));
+            assert_eq!(divm(&a, &b, &m), expected);
+            if let Some(q) = expected {
+                assert_eq!(q.mulm(b, &m), a % m);
+            }
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let m = (BigUint::from(1u8) << 127u32) - 1u8;
+            let a = BigUint::from(random::<u128>());
+            let b = BigUint::from(random::<u64>());
+            let q = divm(&a, &b, &m).unwrap();
+            assert_eq!((&q).mulm(&b, &m), a % &m);
+        }
+    }
+
+    #[test]
+    fn is_riesel_prime_test() {
+        // Lucas-Lehmer test
+        assert_eq!(is_riesel_prime(&1u128, 127), Some(Primality::Yes));
+        assert_eq!(is_riesel_prime(&1u128, 67), Some(Primality::No));
+        assert_eq!(is_riesel_prime(&6u32, 1), Some(Primality::Yes)); // 3 * 2^2 - 1 = 11
+
+        // N doesn't fit in the integer type
+        assert_eq!(is_riesel_prime(&1u64, 64), None);
+        assert_eq!(is_riesel_prime(&3u64, 63), None);
+        assert_eq!(is_riesel_prime(&3u8, 7), None);
+        assert_eq!(is_riesel_prime(&2u8, 7), None);
+        assert_eq!(is_riesel_prime(&1u8, 8), None);
+        assert_eq!(is_riesel_prime(&1u8, 7), Some(Primality::Yes));
+        assert_eq!(is_riesel_prime(&3u16, 14), Some(Primality::No)); // 49151 = 23 * 2137
+
+        // compare with the general test
+        for k in 1..100u64 {
+            for n in 0..24u32 {
+                let target = (k << n) - 1;
+                let expected = match is_prime64(target) {
+                    true => Some(Primality::Yes),
+                    false => Some(Primality::No),
+                };
+                assert_eq!(is_riesel_prime(&k, n), expected, "{} * 2^{} - 1", k, n);
+            }
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            // https://oeis.org/A002235, 3 * 2^n - 1 is a prime
+            let k = BigUint::from(3u8);
+            let is_riesel = |&n: &u32| is_riesel_prime(&k, n) == Some(Primality::Yes);
+            let primes: Vec<u32> = (100..220).filter(is_riesel).collect();
+            assert_eq!(primes, [103, 143, 206, 216]);
+        }
+    }
+
+    #[test]
+    fn prime_constellations_test() {
+        // OEIS A007530
+        let quadruplets = prime_constellations(&[0, 2, 6, 8], 30000);
+        let starts: Vec<u64> = quadruplets.iter().map(|c| c[0]).collect();
+        let expected = [
+            5, 11, 101, 191, 821, 1481, 1871, 2081, 3251, 3461, 5651, 9431, 13001, 15641, 15731,
+            16061, 18041, 18911, 19421, 21011, 22271, 25301,
+        ];
+        assert_eq!(starts, expected);
+        assert_eq!(quadruplets[0], [5, 7, 11, 13]);
+
+        // twin primes
+        let twins = prime_constellations(&[0, 2], 100);
+        assert_eq!(twins.len(), 8);
+        assert_eq!(twins[7], [71, 73]);
+
+        // the inadmissible pattern [0, 2, 4] only matches 3, 5, 7
+        assert_eq!(prime_constellations(&[0, 2, 4], 100000), [[3, 5, 7]]);
+        assert_eq!(prime_constellations(&[], 10).len(), 4);
+        assert!(prime_constellations(&[0, 2], 2).is_empty());
+    }
+
+    #[test]
+    fn prime_pi_lehmer_test() {
+        // OEIS A006880
+        let expected = [
+            0, 4, 25, 168, 1229, 9592, 78498, 664579, 5761455, 50847534, 455052511,
+        ];
+        for (k, &pi) in expected.iter().enumerate() {
+            assert_eq!(prime_pi_lehmer(10u64.pow(k as u32)), pi);
+        }
+
+        // compare with sieving
+        for x in [0, 1, 2, 3, 65535, 65536, 65537, 1 << 20, 1234567] {
+            assert_eq!(prime_pi_lehmer(x), prime_pi(x), "{}", x);
+        }
+        for _ in 0..10 {
+            let x = random::<u64>() % 10000000;
+            assert_eq!(prime_pi_lehmer(x), prime_pi(x), "{}", x);
+        }
+    }
+
+    #[test]
+    fn factors_up_to_size_test() {
+        fn product<T: PrimalityBase>(fac: &BTreeMap<T, usize>, rest: &Option<T>) -> T {
+            let rest = rest.clone().unwrap_or_else(T::one);
+            fac.iter()
+                .fold(rest, |acc, (p, &e)| acc * num_traits::pow(p.clone(), e))
+        }
+
+        // u64 inputs
+        let n = 2u64.pow(5) * 3 * 65537 * 1000000007;
+        let (fac, rest) = factors_up_to_size(n, 20, None);
+        assert_eq!(fac, BTreeMap::from([(2, 5), (3, 1), (65537, 1)]));
+        assert_eq!(rest, Some(1000000007));
+        assert_eq!(factors_up_to_size(n, 32, None), (factorize64(n), None));
+        let (fac, rest) = factors_up_to_size(0u64, 32, None);
+        assert!(fac.is_empty() && rest == Some(0));
+
+        // the large prime cofactor is returned intact
+        let p = 18446744073709551557u128; // largest prime under 2^64
+        let n = 12 * 1000003 * p;
+        let (fac, rest) = factors_up_to_size(n, 32, None);
+        assert_eq!(fac, BTreeMap::from([(2, 2), (3, 1), (1000003, 1)]));
+        assert_eq!(rest, Some(p));
+        assert_eq!(product(&fac, &rest), n);
+
+        // medium factors are found, but the large ones are not
+        let q = next_prime(&(1u128 << 36), None).unwrap();
+        let n = 7 * 1000003 * q * p;
+        let (fac, rest) = factors_up_to_size(n, 40, None);
+        assert_eq!(fac, BTreeMap::from([(7, 1), (1000003, 1), (q, 1)]));
+        assert_eq!(rest, Some(p));
+        let (fac, rest) = factors_up_to_size(n, 32, None);
+        assert_eq!(fac, BTreeMap::from([(7, 1), (1000003, 1)]));
+        assert_eq!(rest, Some(q * p));
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let m127 = BigUint::from(u128::MAX >> 1);
+            let n = &m127 * &m127 * 1000003u32 * 25u8;
+            let (fac, rest) = factors_up_to_size(n.clone(), 64, None);
+            let expected = [(BigUint::from(5u8), 2), (BigUint::from(1000003u32), 1)];
+            assert_eq!(fac, BTreeMap::from(expected));
+            assert_eq!(rest, Some(&m127 * &m127));
+            assert_eq!(product(&fac, &rest), n);
+        }
+
+        // the hard semiprime is left intact when both factors exceed the bound
+        let (p, q) = (4503599627370449u128, 4503599627370353u128); // primes under 2^52
+        let n = 2 * 3 * 3 * 65521 * p * q;
+        for bits in [16, 24, 40] {
+            let (fac, rest) = factors_up_to_size(n, bits, None);
+            assert_eq!(fac, BTreeMap::from([(2, 1), (3, 2), (65521, 1)]));
+            assert_eq!(rest, Some(p * q));
+        }
+        let (fac, rest) = factors_up_to_size(n, 8, None);
+        assert_eq!(fac, BTreeMap::from([(2, 1), (3, 2)]));
+        assert_eq!(rest, Some(65521 * p * q));
+        let (fac, rest) = factors_up_to_size(1u64 << 20, 0, None);
+        assert!(fac.is_empty() && rest == Some(1 << 20));
+        let (fac, rest) = factors_up_to_size(250u8, 4, None);
+        assert_eq!((fac, rest), (BTreeMap::from([(2, 1), (5, 3)]), None));
+    }
+
+    #[test]
+    fn r2_test() {
+        assert_eq!(r2(0), 1);
+        assert_eq!(r2(1), 4);
+        assert_eq!(r2(2), 4);
+        assert_eq!(r2(3), 0);
+        assert_eq!(r2(5), 8);
+        assert_eq!(r2(9), 4);
+        assert_eq!(r2(25), 12);
+        assert_eq!(r2(65), 16);
+        assert_eq!(r2(1000000007), 0);
+        assert_eq!(r2(998244353), 8);
+
+        // count the lattice points directly
+        for n in 0..2000u64 {
+            let m = n.sqrt() as i64;
+            let count = (-m..=m)
+                .flat_map(|a| (-m..=m).map(move |b| a * a + b * b))
+                .filter(|&v| v == n as i64)
+                .count();
+            assert_eq!(r2(n), count as u64, "{}", n);
+        }
+    }
+
+    #[test]
+    fn ramanujan_primes_test() {
+        // OEIS A104272
+        let expected = [
+            2, 11, 17, 29, 41, 47, 59, 67, 71, 97, 101, 107, 127, 149, 151, 167, 179, 181, 227,
+            229, 233, 239, 241, 263, 269, 281, 307, 311, 347, 349, 367, 373, 401, 409, 419, 431,
+            433, 439, 461, 487, 491,
+        ];
+        assert_eq!(ramanujan_primes(expected.len()), expected);
+        assert_eq!(ramanujan_primes(5), expected[..5]);
+        assert!(ramanujan_primes(0).is_empty());
+
+        // check the definition
+        let r = ramanujan_primes(1000);
+        assert!(r.iter().all(|&p| is_prime64(p)));
+        let pi = |x: u64| prime_pi(x) as usize;
+        for k in [1, 10, 100, 1000] {
+            let rk = r[k - 1];
+            assert!(pi(rk - 1) - pi((rk - 1) / 2) < k);
+            assert!((rk..rk + 1000).all(|x| pi(x) - pi(x / 2) >= k));
+        }
+    }
+
+    #[test]
+    fn k_almost_primes_test() {
+        // https://oeis.org/A001358
+        let semiprimes = [
+            4, 6, 9, 10, 14, 15, 21, 22, 25, 26, 33, 34, 35, 38, 39, 46, 49, 51, 55, 57, 58, 62,
+            65, 69, 74, 77, 82, 85, 86, 87, 91, 93, 94, 95,
+        ];
+        assert_eq!(k_almost_primes(2, 95), semiprimes);
+        // https://oeis.org/A014612
+        assert_eq!(
+            k_almost_primes(3, 50),
+            [8, 12, 18, 20, 27, 28, 30, 42, 44, 45, 50]
+        );
+        assert_eq!(k_almost_primes(0, 10), [1]);
+        assert_eq!(k_almost_primes(10, 1024), [1024]);
+        assert!(k_almost_primes(10, 1023).is_empty());
+        assert!(k_almost_primes(64, u64::MAX).is_empty());
+
+        let primes: Vec<u64> = NaiveBuffer::new().primes(10000).cloned().collect();
+        assert_eq!(k_almost_primes(1, 10000), primes);
+        for n in [1000u64, 4096] {
+            let counts: usize = (0..13).map(|k| k_almost_primes(k, n).len()).sum();
+            assert_eq!(counts as u64, n);
+        }
+        for n in k_almost_primes(4, 5000) {
+            assert_eq!(factorize64(n).values().sum::<usize>(), 4);
+        }
+    }
+
+    #[test]
+    fn totient_sum_test() {
+        // OEIS A002088
+        let expected = [0, 1, 2, 4, 6, 10, 12, 18, 22, 28, 32, 42, 46, 58, 64, 72];
+        for (n, v) in expected.iter().enumerate() {
+            assert_eq!(totient_sum(n as u64), *v);
+        }
+        for n in [1000u64, 4095, 4096, 4097, 10000, 123456, 1000000] {
+            assert_eq!(totient_sum_fast(n), totient_sum(n), "{}", n);
+        }
+        for _ in 0..5 {
+            let n = random::<u64>() % 1000000;
+            assert_eq!(totient_sum_fast(n), totient_sum(n), "{}", n);
+        }
+
+        // OEIS A064018
+        assert_eq!(totient_sum_fast(10u64.pow(9)), 303963551173008414);
+    }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn is_prime_str_test() {
+        assert_eq!(is_prime_str("2", None), Ok(Primality::Yes));
+        assert_eq!(is_prime_str(" 1000000007\n", None), Ok(Primality::Yes));
+        assert_eq!(is_prime_str("1000000008", None), Ok(Primality::No));
+        assert_eq!(is_prime_str("0", None), Ok(Primality::No));
+
+        // 2^1279 - 1 is a Mersenne prime with 386 digits
+        let m1279: BigUint = (BigUint::from(1u8) << 1279usize) - 1u8;
+        assert!(is_prime_str(&m1279.to_string(), None).unwrap().probably());
+        let c = m1279 * 3u8;
+        assert_eq!(is_prime_str(&c.to_string(), None), Ok(Primality::No));
+
+        // malformed input
+        for s in ["", "  ", "abc", "12a3", "-7", "1.5", "0x1f"] {
+            assert!(is_prime_str(s, None).is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn factor_tree_test() {
+        fn check<T: PrimalityBase + std::fmt::Debug>(target: T)
+        where
+            for<'r> &'r T: PrimalityRefBase<T>,
+        {
+            let tree = factor_tree(target.clone(), None);
+            assert_eq!(tree.value(), &target);
+            let leaves = tree.leaves();
+            assert!(leaves
+                .iter()
+                .all(|l| matches!(l, FactorTree::Prime(p) if is_prime(p, None).probably())));
+            let product = leaves.iter().fold(T::one(), |acc, l| acc * l.value());
+            assert_eq!(product, target);
+        }
+
+        check(2u8);
+        check(255u8);
+        check(8051u16);
+        check(1000000007u64 * 998244353);
+        check(2u64.pow(20) * 3u64.pow(10));
+        check(104729u128 * 104729 * 1000000007 * 998244353);
+        for _ in 0..10 {
+            check(random::<u64>() | 1);
+        }
+        #[cfg(feature = "num-bigint")]
+        check(BigUint::from(u128::MAX >> 1) * 1000000007u64 * 6u8);
+
+        // the structure of the splits
+        let tree = factor_tree(12u32, None);
+        let (two, three) = (FactorTree::Prime(2), FactorTree::Prime(3));
+        let six = FactorTree::Composite {
+            value: 6,
+            divisor: Box::new(two.clone()),
+            cofactor: Box::new(three),
+        };
+        let expected = FactorTree::Composite {
+            value: 12,
+            divisor: Box::new(two),
+            cofactor: Box::new(six),
+        };
+        assert_eq!(tree, expected);
+
+        assert_eq!(factor_tree(0u32, None), FactorTree::Unfactored(0));
+        assert_eq!(factor_tree(1u32, None), FactorTree::Unfactored(1));
+    }
+
+    #[test]
+    fn next_prime_with_gap_test() {
+        assert_eq!(next_prime_with_gap(&0u8, None), Some((2, 2)));
+        assert_eq!(next_prime_with_gap(&2u8, None), Some((3, 1)));
+        assert_eq!(next_prime_with_gap(&7u8, None), Some((11, 4)));
+        assert_eq!(next_prime_with_gap(&113u32, None), Some((127, 14)));
+        assert_eq!(next_prime_with_gap(&251u8, None), None);
+        assert_eq!(next_prime_with_gap(&1327u64, None), Some((1361, 34)));
+
+        for _ in 0..10 {
+            let target = random::<u32>() as u64;
+            let (p, gap) = next_prime_with_gap(&target, None).unwrap();
+            assert_eq!(Some(p), next_prime(&target, None));
+            assert_eq!(target + gap, p);
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let target = BigUint::from(1u8) << 100u32;
+            let (p, gap) = next_prime_with_gap(&target, None).unwrap();
+            assert_eq!(p, target + gap);
+        }
+    }
+
+    #[test]
+    fn next_prime_with_progress_test() {
+        let wheel = WHEEL_SIZE as u64;
+        for _ in 0..10 {
+            let target = random::<u32>() as u64;
+            let mut candidates = Vec::new();
+            let p = next_prime_with_progress(&target, None, |&c| {
+                candidates.push(c);
+                true
+            });
+            assert_eq!(p, next_prime(&target, None));
+
+            // all the integers on the wheel are tested
+            let expected: Vec<u64> = (target + 1..=p.unwrap())
+                .filter(|&n| num_integer::gcd(n, wheel) == 1)
+                .collect();
+            assert_eq!(candidates, expected);
+        }
+
+        // small primes are looked up in the table
+        let mut count = 0;
+        let p = next_prime_with_progress(&100u8, None, |_| {
+            count += 1;
+            true
+        });
+        assert_eq!((p, count), (Some(101), 1));
+        assert_eq!(next_prime_with_progress(&251u8, None, |_| true), None);
+        let p = next_prime_with_progress(&65500u16, None, |_| true);
+        assert_eq!(p, Some(65519));
+        assert_eq!(next_prime_with_progress(&65521u16, None, |_| true), None);
+
+        // abort the search
+        let mut count = 0;
+        let p = next_prime_with_progress(&((1u128 << 100) + 1), None, |_| {
+            count += 1;
+            count < 3
+        });
+        assert_eq!((p, count), (None, 3));
+    }
+
+    #[test]
+    fn try_from_u64_test() {
+        assert_eq!(try_from_u64::<u8>(255), Some(255));
+        assert_eq!(try_from_u64::<u8>(256), None);
+        assert_eq!(try_from_u64::<u16>(65535), Some(65535));
+        assert_eq!(try_from_u64::<u32>(u64::MAX), None);
+        assert_eq!(try_from_u64::<u128>(u64::MAX), Some(u64::MAX as u128));
+        #[cfg(feature = "num-bigint")]
+        assert_eq!(try_from_u64(u64::MAX), Some(BigUint::from(u64::MAX)));
+    }
+
+    #[test]
+    fn least_prime_in_ap_test() {
+        assert_eq!(least_prime_in_ap(1, 10), Some(11));
+        assert_eq!(least_prime_in_ap(3, 10), Some(3));
+        assert_eq!(least_prime_in_ap(9, 10), Some(19));
+        assert_eq!(least_prime_in_ap(21, 10), Some(11));
+        assert_eq!(least_prime_in_ap(1, 1), Some(2));
+        assert_eq!(least_prime_in_ap(1, 100), Some(101));
+        assert_eq!(least_prime_in_ap(1, 1000), Some(3001));
+
+        // OEIS A034694: smallest prime = 1 (mod n)
+        let expected = [
+            2, 3, 7, 5, 11, 7, 29, 17, 19, 11, 23, 13, 53, 29, 31, 17, 103, 19,
+        ];
+        for (n, p) in expected.iter().enumerate() {
+            assert_eq!(least_prime_in_ap(1, n as u64 + 1), Some(*p));
+        }
+
+        // gcd(a, m) > 1
+        assert_eq!(least_prime_in_ap(2, 4), Some(2));
+        assert_eq!(least_prime_in_ap(6, 4), Some(2));
+        assert_eq!(least_prime_in_ap(4, 6), None);
+        assert_eq!(least_prime_in_ap(7, 0), Some(7));
+    }
+
+    #[test]
+    fn decimal_period_test() {
+        assert_eq!(decimal_period(7, 10), 6);
+        assert_eq!(decimal_period(3, 10), 1);
+        assert_eq!(decimal_period(11, 10), 2);
+        assert_eq!(decimal_period(13, 10), 6);
+        assert_eq!(decimal_period(17, 10), 16);
+        assert_eq!(decimal_period(81, 10), 9);
+        assert_eq!(decimal_period(9801, 10), 198);
+
+        // terminating expansions
+        assert_eq!(decimal_period(1, 10), 0);
+        assert_eq!(decimal_period(8, 10), 0);
+        assert_eq!(decimal_period(250, 10), 0);
+
+        // factors shared with the base only give a non-repeating prefix
+        assert_eq!(decimal_period(14, 10), 6); // 1/14 = 0.0(714285)
+        assert_eq!(decimal_period(12, 10), 1); // 1/12 = 0.08(3)
+        assert_eq!(decimal_period(56, 10), 6);
+
+        // other bases
+        assert_eq!(decimal_period(3, 2), 2); // 1/3 = 0.(01) in binary
+        assert_eq!(decimal_period(7, 2), 3);
+        assert_eq!(decimal_period(10, 2), 4);
+        assert_eq!(decimal_period(5, 12), 4);
+    }
+
+    #[test]
+    fn primes_in_ap_test() {
+        assert_eq!(primes_in_ap(3, 4, 8), [3, 7, 11, 19, 23, 31, 43, 47]);
+        assert_eq!(primes_in_ap(1, 10, 5), [11, 31, 41, 61, 71]);
+        let p = primes_in_ap(199, 210, 10);
+        assert_eq!(p, [199, 409, 619, 829, 1039, 1249, 1459, 1669, 1879, 2089]);
+        assert_eq!(primes_in_ap(2, 1, 5), [2, 3, 5, 7, 11]);
+        assert!(primes_in_ap(3, 4, 0).is_empty());
+
+        // gcd(first, diff) > 1
+        assert_eq!(primes_in_ap(3, 6, 5), [3]);
+        assert!(primes_in_ap(4, 6, 5).is_empty());
+        assert_eq!(primes_in_ap(7, 0, 3), [7]);
+        assert!(primes_in_ap(1, 0, 3).is_empty());
+
+        // stop at the end of u64 range
+        let p = primes_in_ap(u64::MAX - 100, 2, 10);
+        assert_eq!(p, [u64::MAX - 94, u64::MAX - 82, u64::MAX - 58]);
+    }
+
+    #[test]
+    fn truncatable_prime_test() {
+        for p in [2, 3, 5, 7] {
+            assert!(is_truncatable_prime(p, true, true));
+        }
+        assert!(!is_truncatable_prime(1, true, true));
+
+        // the largest right-truncatable prime
+        assert!(is_truncatable_prime(73939133, false, true));
+        assert!(!is_truncatable_prime(73939133, true, false));
+
+        // left-truncatable primes, OEIS:A024785
+        assert!(is_truncatable_prime(9137, true, false));
+        assert!(!is_truncatable_prime(9137, false, true));
+        assert!(!is_truncatable_prime(103, true, false)); // contains zero
+
+        // two-sided primes, OEIS:A020994
+        for p in [23, 37, 53, 73, 313, 317, 373, 797, 3137, 3797, 739397] {
+            assert!(is_truncatable_prime(p, true, true), "{}", p);
+        }
+        assert!(!is_truncatable_prime(29, true, true));
+    }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn repunit_test() {
+        assert_eq!(repunit(0), BigUint::from(0u8));
+        assert_eq!(repunit(4), BigUint::from(1111u16));
+
+        // OEIS:A004023
+        assert_eq!(is_repunit_prime(2), Primality::Yes);
+        assert_eq!(is_repunit_prime(19), Primality::Yes);
+        assert!(matches!(is_repunit_prime(23), Primality::Probable(_)));
+        assert_eq!(is_repunit_prime(3), Primality::No);
+        assert_eq!(is_repunit_prime(4), Primality::No);
+        assert!(!is_repunit_prime(29).probably());
+    }
+
+    #[test]
+    fn ilog_test() {
+        assert_eq!(ilog(&1u32, &2), 0);
+        assert_eq!(ilog(&1000u32, &10), 3); // f64 gives 2.9999999999999996
+        assert_eq!(ilog(&999u32, &10), 2);
+        assert_eq!(ilog(&u64::MAX, &2), 63);
+        assert_eq!(ilog(&u64::MAX, &u64::MAX), 1);
+        assert_eq!(ilog(&(u64::MAX - 1), &u64::MAX), 0);
+        assert_eq!(ilog(&u128::MAX, &3), 80);
+
+        for n in 1u64..2000 {
+            for b in 2u64..20 {
+                assert_eq!(ilog(&n, &b), n.ilog(b), "ilog({}, {})", n, b);
+            }
+        }
+        for _ in 0..100 {
+            let n = random::<u64>() | 1;
+            let b = random::<u8>() as u64 + 2;
+            let est = (n as f64).log(b as f64);
+            if (est - est.round()).abs() > 1e-6 {
+                // skip the values where floating point is not exact
+                assert_eq!(ilog(&n, &b), est.floor() as u32);
+            }
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let n = BigUint::from(10u8).pow(100u32);
+            assert_eq!(ilog(&n, &BigUint::from(10u8)), 100);
+            assert_eq!(ilog(&(n - 1u8), &BigUint::from(10u8)), 99);
+        }
+    }
+
+    #[test]
+    fn farey_neighbors_test() {
+        assert_eq!(farey_neighbors(5, 1, 2), ((2, 5), (3, 5)));
+        assert_eq!(farey_neighbors(5, 1, 3), ((1, 4), (2, 5)));
+        assert_eq!(farey_neighbors(5, 1, 5), ((0, 1), (1, 4)));
+        assert_eq!(farey_neighbors(5, 4, 5), ((3, 4), (1, 1)));
+
+        // compare with brute force search
+        let n = 30u64;
+        let mut seq: Vec<(u64, u64)> = (1..=n)
+            .flat_map(|q| (0..=q).map(move |p| (p, q)))
+            .filter(|&(p, q)| num_integer::gcd(p, q) == 1)
+            .collect();
+        seq.sort_by(|&(a, b), &(c, d)| (a * d).cmp(&(c * b)));
+        for w in seq.windows(3) {
+            assert_eq!(farey_neighbors(n, w[1].0, w[1].1), (w[0], w[2]));
+        }
+    }
+
+    #[test]
+    fn farey_sequence_test() {
+        assert!(farey_sequence(0).is_empty());
+        assert_eq!(farey_sequence(1), [(0, 1), (1, 1)]);
+        assert_eq!(
+            farey_sequence(5),
+            [
+                (0, 1),
+                (1, 5),
+                (1, 4),
+                (1, 3),
+                (2, 5),
+                (1, 2),
+                (3, 5),
+                (2, 3),
+                (3, 4),
+                (4, 5),
+                (1, 1)
+            ]
+        );
+
+        for n in 1..50u64 {
+            let seq = farey_sequence(n);
+            assert_eq!(seq.len() as u128, totient_sum(n) + 1);
+            for w in seq.windows(2) {
+                assert_eq!(w[1].0 * w[0].1 - w[0].0 * w[1].1, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn poly_prime_run_test() {
+        assert_eq!(poly_prime_run(1, 1, 41, 100), 40); // Euler
+        assert_eq!(poly_prime_run(1, -79, 1601, 100), 80);
+        assert_eq!(poly_prime_run(1, 1, 41, 10), 10);
+        assert_eq!(poly_prime_run(2, 0, 29, 100), 29); // Legendre
+        assert_eq!(poly_prime_run(1, 0, 1, 100), 0);
+        assert_eq!(poly_prime_run(0, 0, -7, 5), 5); // constant polynomial
+        assert_eq!(poly_prime_run(i64::MAX, 0, 2, 5), 1); // overflow
+    }
+
+    #[test]
+    fn primitive_pythagorean_triples_test() {
+        assert!(primitive_pythagorean_triples(11).is_empty());
+        assert_eq!(primitive_pythagorean_triples(12), vec![(3, 4, 5)]);
+
+        let triples = primitive_pythagorean_triples(100);
+        assert_eq!(
+            triples,
+            vec![
+                (3, 4, 5),
+                (5, 12, 13),
+                (7, 24, 25),
+                (8, 15, 17),
+                (9, 40, 41),
+                (12, 35, 37),
+                (20, 21, 29)
+            ]
+        );
+
+        let triples = primitive_pythagorean_triples(10000);
+        assert_eq!(triples.len(), 703); // OEIS:A024364
+        for (a, b, c) in triples {
+            assert_eq!(a * a + b * b, c * c);
+            assert!(a + b + c <= 10000);
+            assert_eq!(num_integer::gcd(num_integer::gcd(a, b), c), 1);
+        }
+    }
+
+    #[test]
+    fn primitive_root_test() {
+        let phi: Vec<u64> = (0..13).map(euler_phi).collect();
+        assert_eq!(phi, [0, 1, 1, 2, 2, 4, 2, 6, 4, 6, 4, 10, 4]);
+
+        // https://oeis.org/A046145
+        let roots = [
+            0, 0, 1, 2, 3, 2, 5, 3, 0, 2, 3, 2, 0, 2, 3, 0, 0, 3, 5, 2, 0, 0, 7, 5,
+        ];
+        for (n, &r) in roots.iter().enumerate().skip(1) {
+            let expected = if r == 0 && n > 1 { None } else { Some(r) };
+            assert_eq!(primitive_root(n as u64), expected);
+        }
+        assert_eq!(primitive_root(0), None);
+        assert_eq!(primitive_root(998244353), Some(3));
+        assert_eq!(primitive_root(1000000007), Some(5));
+        assert_eq!(primitive_root(2 * 3u64.pow(20)), Some(5));
+        assert_eq!(primitive_root(3 * 5 * 7), None);
+
+        let table = primitive_root_table(2000);
+        assert_eq!(table.len(), 2001);
+        for (n, &r) in table.iter().enumerate() {
+            assert_eq!(r.map(u64::from), primitive_root(n as u64));
+        }
+    }
 
-        let fac1_17 = BTreeMap::from_iter([(2071723, 1), (5363222357, 1)]);
-        let fac = factorize64(11111111111111111);
-        assert_eq!(fac, fac1_17);
+    #[test]
+    fn find_ntt_prime_test() {
+        assert_eq!(find_ntt_prime(29, 23), Some((595591169, 3)));
+        assert_eq!(find_ntt_prime(0, 0), Some((2, 1)));
+        assert_eq!(find_ntt_prime(2, 1), Some((5, 2)));
+        assert_eq!(find_ntt_prime(62, 60), None);
+        assert_eq!(find_ntt_prime(65, 1), None);
 
-        // perfect powers
-        for exp in 2u32..5 {
-            assert_eq!(
-                factorize128(8167u128.pow(exp)),
-                BTreeMap::from_iter([(8167, exp as usize)])
-            );
+        for (bits, k) in [(10, 4), (20, 10), (32, 20), (50, 30), (63, 40)] {
+            let (p, g) = find_ntt_prime(bits, k).unwrap();
+            assert!(is_prime64(p) && p >> bits > 0);
+            assert_eq!((p - 1) % (1 << k), 0);
+
+            // g^((p-1)/2^k) is a primitive 2^k-th root of unity
+            let w = g.powm((p - 1) >> k, &p);
+            assert_eq!(w.powm(1 << k, &p), 1);
+            assert_eq!(w.powm(1 << (k - 1), &p), p - 1);
         }
+    }
 
-        // 100 random factorization tests
-        for _ in 0..100 {
-            let x = random();
-            let fac = factorize64(x);
-            let mut prod = 1;
-            for (p, exp) in fac {
-                assert!(
-                    is_prime64(p),
-                    "factorization result should have prime factors! (get {})",
-                    p
-                );
-                prod *= p.pow(exp as u32);
+    #[test]
+    fn multiplicative_order_test() {
+        assert_eq!(multiplicative_order(&2u32, &7), Some(3));
+        assert_eq!(multiplicative_order(&3u32, &10), Some(4));
+        assert_eq!(multiplicative_order(&10u32, &7), Some(6));
+        assert_eq!(multiplicative_order(&1u32, &1), Some(1));
+        assert_eq!(multiplicative_order(&4u32, &10), None);
+        assert_eq!(multiplicative_order(&5u64, &1000000007), Some(1000000006));
+        assert_eq!(multiplicative_order(&2u64, &(1 << 61)), None);
+        assert_eq!(multiplicative_order(&3u64, &(1 << 61)), Some(1 << 59));
+
+        // order_dividing with φ(n) agrees with multiplicative_order
+        for n in 2..300u64 {
+            let phi = euler_phi(n);
+            let phi_factors = factorize64(phi);
+            for a in (1..n).filter(|a| num_integer::gcd(*a, n) == 1) {
+                let order = order_dividing(&a, &n, &phi, &phi_factors);
+                assert_eq!(Some(order), multiplicative_order(&a, &n));
+                assert_eq!(a.powm(order, &n), 1 % n);
             }
-            assert_eq!(x, prod, "factorization check failed! ({} != {})", x, prod);
         }
+
+        // works with a multiple of the group order
+        let p = 998244353u64;
+        let m = (p - 1) * 15;
+        assert_eq!(order_dividing(&3, &p, &m, &factorize64(m)), p - 1);
     }
 
     #[test]
-    fn factorize128_test() {
-        // some simple cases
-        let fac_primorial19 =
-            BTreeMap::from_iter(SMALL_PRIMES.iter().take(19).map(|&p| (p as u128, 1)));
-        let fac = factorize128(7858321551080267055879090);
-        assert_eq!(fac, fac_primorial19);
+    fn sopf_test() {
+        assert_eq!(sopf(&12u32), 5);
+        assert_eq!(sopfr(&12u32), 7);
+        assert_eq!(sopf(&1u32), 0);
+        assert_eq!(sopfr(&1u32), 0);
 
-        let fac_smallbig = BTreeMap::from_iter([(167, 1), (2417851639229258349412369, 1)]);
-        let fac = factorize128(403781223751286144351865623);
-        assert_eq!(fac, fac_smallbig);
+        // https://oeis.org/A008472 and https://oeis.org/A001414
+        let s: Vec<u64> = (1..13).map(|n| sopf(&n)).collect();
+        assert_eq!(s, [0, 2, 3, 2, 5, 5, 7, 2, 3, 7, 11, 5]);
+        let s: Vec<u64> = (1..13).map(|n| sopfr(&n)).collect();
+        assert_eq!(s, [0, 2, 3, 4, 5, 5, 7, 6, 6, 7, 11, 7]);
+        assert_eq!(sopfr(&1024u16), 20);
+        assert_eq!(sopfr(&((1u128 << 89) - 1)), (1u128 << 89) - 1);
+    }
 
-        // perfect powers
-        for exp in 5u32..10 {
-            // 2^64 < 8167^5 < 8167^9 < 2^128
-            assert_eq!(
-                factorize128(8167u128.pow(exp)),
-                BTreeMap::from_iter([(8167, exp as usize)])
-            );
+    #[test]
+    fn ruth_aaron_pair_test() {
+        assert!(is_ruth_aaron_pair(714));
+        assert!(!is_ruth_aaron_pair(713));
+        assert!(!is_ruth_aaron_pair(715));
+        assert!(!is_ruth_aaron_pair(0));
+        assert!(!is_ruth_aaron_pair(1));
+        assert!(!is_ruth_aaron_pair(u64::MAX));
+
+        // https://oeis.org/A039752
+        let pairs = ruth_aaron_pairs(3000);
+        assert_eq!(pairs, [5, 8, 15, 77, 125, 714, 948, 1330, 1520, 1862, 2491]);
+        assert!(pairs.iter().all(|&n| is_ruth_aaron_pair(n)));
+        assert!(ruth_aaron_pairs(5).is_empty());
+        assert_eq!(ruth_aaron_pairs(6), [5]);
+    }
+
+    #[test]
+    fn divisor_sigma_test() {
+        // https://oeis.org/A000005 and https://oeis.org/A000203
+        let d0: Vec<u64> = (1..13).map(|n| divisor_sigma(&n, 0)).collect();
+        assert_eq!(d0, [1, 2, 2, 3, 2, 4, 2, 4, 3, 4, 2, 6]);
+        let d1: Vec<u64> = (1..13).map(|n| divisor_sigma(&n, 1)).collect();
+        assert_eq!(d1, [1, 3, 4, 7, 6, 12, 8, 15, 13, 18, 12, 28]);
+        assert_eq!(divisor_sigma(&12u64, 2), 1 + 4 + 9 + 16 + 36 + 144);
+        for n in 1..500u64 {
+            let sigma: u64 = (1..=n).filter(|d| n % d == 0).map(|d| d * d * d).sum();
+            assert_eq!(divisor_sigma(&n, 3), sigma);
         }
 
-        // random factorization tests
-        for _ in 0..4 {
-            let x = random::<u128>() >> 28; // test 100 bit numbers
-            let fac = factorize128(x);
-            let mut prod = 1;
-            for (p, exp) in fac {
-                assert!(
-                    is_prime(&p, None).probably(),
-                    "factorization result should have prime factors! (get {})",
-                    p
-                );
-                prod *= p.pow(exp as u32);
-            }
-            assert_eq!(x, prod, "factorization check failed! ({} != {})", x, prod);
+        assert_eq!(abundancy_index(&6u64), (2, 1));
+        assert_eq!(abundancy_index(&28u64), (2, 1));
+        assert_eq!(abundancy_index(&4u64), (7, 4));
+        assert_eq!(abundancy_index(&1u32), (1, 1));
+        assert!(are_friendly(6, 28));
+        assert!(are_friendly(30, 140));
+        assert!(!are_friendly(6, 12));
+        // 4 is solitary
+        assert!((5..10000).all(|n| !are_friendly(4, n)));
+    }
+
+    #[test]
+    fn gcd_lcm_many_test() {
+        assert_eq!(gcd_many::<u32>(&[]), 0);
+        assert_eq!(lcm_many::<u32>(&[]), Some(1));
+        assert_eq!(gcd_many(&[12u32]), 12);
+        assert_eq!(lcm_many(&[12u32]), Some(12));
+        assert_eq!(gcd_many(&[12u32, 18, 30]), 6);
+        assert_eq!(lcm_many(&[12u32, 18, 30]), Some(180));
+        assert_eq!(gcd_many(&[0u32, 0, 15, 0, 25]), 5);
+        assert_eq!(lcm_many(&[3u32, 0, 5]), Some(0));
+        assert_eq!(gcd_many(&[7u64, 11, 13]), 1);
+
+        // lcm(1, ..., 20) and overflow
+        let v: Vec<u64> = (1..=20).collect();
+        assert_eq!(lcm_many(&v), Some(232792560));
+        let v: Vec<u64> = (1..=42).collect();
+        assert_eq!(lcm_many(&v), Some(219060189739591200));
+        let v: Vec<u64> = (1..=47).collect();
+        assert_eq!(lcm_many(&v), None);
+        assert_eq!(lcm_many(&[1u8 << 7, 3]), None);
+        assert_eq!(lcm_many(&[1u8 << 7, 1 << 6, 2]), Some(128));
+    }
+
+    #[test]
+    fn unit_group_structure_test() {
+        assert_eq!(unit_group_structure(&8u32), [2, 2]);
+        assert_eq!(unit_group_structure(&7u32), [6]);
+        assert!(unit_group_structure(&1u32).is_empty());
+        assert!(unit_group_structure(&2u32).is_empty());
+        assert_eq!(unit_group_structure(&4u32), [2]);
+        assert_eq!(unit_group_structure(&15u32), [2, 4]);
+        assert_eq!(unit_group_structure(&32u32), [2, 8]);
+        assert_eq!(unit_group_structure(&63u32), [6, 6]);
+        assert_eq!(unit_group_structure(&(8u64 * 9 * 5 * 7)), [2, 2, 2, 6, 12]);
+        assert_eq!(unit_group_structure(&1000000007u64), [1000000006]);
+
+        for n in 1..500u64 {
+            let factors = unit_group_structure(&n);
+            assert_eq!(factors.iter().product::<u64>(), euler_phi(n));
+            assert!(factors.windows(2).all(|w| w[1] % w[0] == 0));
+            assert_eq!(factors.len() <= 1, n <= 2 || primitive_root(n).is_some());
         }
     }
 
     #[test]
-    fn is_safe_prime_test() {
-        // OEIS:A005385
-        let safe_primes = [
-            5u16, 7, 11, 23, 47, 59, 83, 107, 167, 179, 227, 263, 347, 359, 383, 467, 479, 503,
-            563, 587, 719, 839, 863, 887, 983, 1019, 1187, 1283, 1307, 1319, 1367, 1439, 1487,
-            1523, 1619, 1823, 1907,
-        ];
-        for p in SMALL_PRIMES {
-            let p = p as u16;
-            if p > 1500 {
-                break;
+    fn totient_chain_length_test() {
+        // https://oeis.org/A003434
+        let expected = [0, 1, 2, 2, 3, 2, 3, 3, 3, 3, 4, 3, 4, 3, 4, 4, 5, 3, 4, 4];
+        for (n, &length) in (1u64..).zip(expected.iter()) {
+            assert_eq!(totient_chain_length(&n), length, "n = {}", n);
+        }
+
+        // compare with repeated euler_phi
+        for n in 1u64..2000 {
+            let mut length = 0;
+            let mut m = n;
+            while m > 1 {
+                m = euler_phi(m);
+                length += 1;
             }
-            assert_eq!(
-                is_safe_prime(&p).probably(),
-                safe_primes.iter().find(|&v| &p == v).is_some()
-            );
+            assert_eq!(totient_chain_length(&n), length);
+        }
+
+        assert_eq!(totient_chain_length(&(1u128 << 100)), 100);
+        #[cfg(feature = "num-bigint")]
+        {
+            // φ(3^k) = 2 * 3^(k-1) and φ(2 * 3^k) = 2 * 3^(k-1)
+            let n = num_traits::pow(BigUint::from(3u8), 50);
+            assert_eq!(totient_chain_length(&n), 51);
         }
     }
 
     #[test]
-    fn moebius_test() {
-        // test small examples
-        let mu20: [i8; 20] = [
-            1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0, -1, 1, 1, 0, -1, 0, -1, 0,
-        ];
-        for i in 0..20 {
-            assert_eq!(moebius(&(i + 1)), mu20[i], "moebius on {}", i);
+    fn unit_group_order_test() {
+        assert_eq!(unit_group_order(&1u32), 1);
+        assert_eq!(unit_group_order(&8u32), 4);
+        assert_eq!(unit_group_order(&1000000007u64), 1000000006);
+        for n in 1..1000u64 {
+            assert_eq!(unit_group_order(&n), euler_phi(n));
         }
 
-        // some square numbers
-        assert_eq!(moebius(&1024u32), 0);
-        assert_eq!(moebius(&(8081u32 * 8081)), 0);
+        // share the factorization with the group structure
+        let factors = factorize(2520u64);
+        let structure = unit_group_structure_factorized(&factors);
+        assert_eq!(structure, unit_group_structure(&2520u64));
+        let order: u64 = structure.iter().product();
+        assert_eq!(order, unit_group_order_factorized(&factors));
 
-        // sphenic numbers
-        let sphenic3: [u8; 20] = [
-            30, 42, 66, 70, 78, 102, 105, 110, 114, 130, 138, 154, 165, 170, 174, 182, 186, 190,
-            195, 222,
-        ]; // OEIS:A007304
-        for i in 0..20 {
-            assert_eq!(moebius(&sphenic3[i]), -1i8, "moebius on {}", sphenic3[i]);
-        }
-        let sphenic5: [u16; 23] = [
-            2310, 2730, 3570, 3990, 4290, 4830, 5610, 6006, 6090, 6270, 6510, 6630, 7410, 7590,
-            7770, 7854, 8610, 8778, 8970, 9030, 9282, 9570, 9690,
-        ]; // OEIS:A046387
-        for i in 0..20 {
-            assert_eq!(moebius(&sphenic5[i]), -1i8, "moebius on {}", sphenic5[i]);
+        #[cfg(feature = "num-bigint")]
+        {
+            let n = BigUint::from(1000000007u64) * BigUint::from(998244353u64);
+            let expected = BigUint::from(1000000006u64) * BigUint::from(998244352u64);
+            assert_eq!(unit_group_order(&n), expected);
         }
     }
 
     #[test]
-    fn prime_pi_bounds_test() {
-        fn check(n: u64, pi: u64) {
-            let (lo, hi) = prime_pi_bounds(&n);
-            let est = prime_pi_est(&n);
-            assert!(
-                lo <= pi && pi <= hi,
-                "fail to satisfy {} <= pi({}) = {} <= {}",
-                lo,
-                n,
-                pi,
-                hi
-            );
-            assert!(lo <= est && est <= hi);
+    fn root_mod_prime_test() {
+        // cube roots with gcd(3, p - 1) = 3
+        assert_eq!(root_mod_prime(&1u32, &3, &7), [1, 2, 4]);
+        assert_eq!(root_mod_prime(&6u32, &3, &7), [3, 5, 6]);
+        assert!(root_mod_prime(&2u32, &3, &7).is_empty());
+        assert_eq!(root_mod_prime(&8u32, &3, &19), [2, 3, 14]);
+
+        // cube roots with gcd(3, p - 1) = 1
+        for a in 1..11u32 {
+            let roots = root_mod_prime(&a, &3, &11);
+            assert_eq!(roots.len(), 1);
+            assert_eq!(roots[0].pow(3) % 11, a);
         }
+        assert_eq!(root_mod_prime(&0u32, &3, &11), [0]);
 
-        // test with sieved primes
-        let mut pb = NaiveBuffer::new();
-        let mut last = 0;
-        for (i, p) in pb.primes(100000).cloned().enumerate() {
-            for j in last..p {
-                check(j, i as u64);
+        // compare with brute force
+        for p in [2u64, 3, 13, 97, 257, 433] {
+            for n in 1..13u64 {
+                for a in 0..p {
+                    let expected: Vec<u64> = (0..p).filter(|x| x.powm(n, &p) == a).collect();
+                    assert_eq!(root_mod_prime(&a, &n, &p), expected);
+                }
             }
-            last = p;
         }
 
-        // test with some known cases with input as 10^n, OEIS:A006880
-        let pow10_values = [
-            0,
-            4,
-            25,
-            168,
-            1229,
-            9592,
-            78498,
-            664579,
-            5761455,
-            50847534,
-            455052511,
-            4118054813,
-            37607912018,
-            346065536839,
-            3204941750802,
-            29844570422669,
-            279238341033925,
-            2623557157654233,
-        ];
-        for (exponent, gt) in pow10_values.iter().enumerate() {
-            let n = 10u64.pow(exponent as u32);
-            check(n, *gt);
+        // large primes
+        let p = 998244353u64;
+        for _ in 0..10 {
+            let x = random::<u64>() % (p - 1) + 1;
+            for n in [2, 12, 1 << 10, 1000003] {
+                let a = x.powm(n, &p);
+                let roots = root_mod_prime(&a, &n, &p);
+                assert_eq!(roots.len() as u64, num_integer::gcd(n, p - 1));
+                assert!(roots.contains(&x));
+                assert!(roots.iter().all(|r| r.powm(n, &p) == a));
+            }
         }
+        let p = 18446744073709551557u128; // largest u64 prime
+        let roots = root_mod_prime(&4u128, &2, &p);
+        assert_eq!(roots, [2, p - 2]);
     }
 
     #[test]
-    fn nth_prime_bounds_test() {
-        fn check(n: u64, p: u64) {
-            let (lo, hi) = super::nth_prime_bounds(&n).unwrap();
-            assert!(
-                lo <= p && p <= hi,
-                "fail to satisfy: {} <= {}-th prime = {} <= {}",
-                lo,
-                n,
-                p,
-                hi
-            );
-            let est = super::nth_prime_est(&n).unwrap();
-            assert!(lo <= est && est <= hi);
-        }
+    fn factorization_hint_test() {
+        use FactorDifficulty::{Easy, Hard, Prime};
+        assert_eq!(factorization_hint(&1000000007u64), Prime);
+        assert_eq!(factorization_hint(&12u32), Easy);
+        assert_eq!(factorization_hint(&1u32), Easy);
+        assert_eq!(factorization_hint(&(1000000007u64 * 998244353)), Easy);
 
-        // test with sieved primes
-        let mut pb = NaiveBuffer::new();
-        for (i, p) in pb.primes(100000).cloned().enumerate() {
-            check(i as u64 + 1, p as u64);
-        }
+        // smooth numbers and semiprimes
+        let p = 18446744073709551557u128; // largest u64 prime
+        assert_eq!(factorization_hint(&(p * 3)), Easy);
+        assert_eq!(factorization_hint(&(1u128 << 100)), Easy);
+        assert_eq!(factorization_hint(&(3u128.pow(70))), Easy);
+        assert_eq!(factorization_hint(&(p * 1000000007)), Hard);
 
-        // test with some known cases with input as 10^n, OEIS:A006988
-        let pow10_values = [
-            2,
-            29,
-            541,
-            7919,
-            104729,
-            1299709,
-            15485863,
-            179424673,
-            2038074743,
-            22801763489,
-            252097800623,
-            2760727302517,
-            29996224275833,
-            323780508946331,
-            3475385758524527,
-            37124508045065437,
-        ];
-        for (exponent, nth_prime) in pow10_values.iter().enumerate() {
-            let n = 10u64.pow(exponent as u32);
-            check(n, *nth_prime);
+        #[cfg(feature = "num-bigint")]
+        {
+            let p = BigUint::from(2u8).pow(89) - 1u8; // M89
+            let q = BigUint::from(2u8).pow(107) - 1u8; // M107
+            assert_eq!(factorization_hint(&p), Prime);
+            assert_eq!(factorization_hint(&(&p * 210u8)), Easy);
+            assert_eq!(factorization_hint(&(&p * &q)), Hard);
         }
     }
 
     #[test]
-    fn prev_next_test() {
-        assert_eq!(prev_prime(&2u32, None), None);
+    fn prime_signature_test() {
+        assert!(prime_signature(&1u32).is_empty());
+        assert_eq!(prime_signature(&12u32), [1, 2]);
+        assert_eq!(prime_signature(&1000u32), [3, 3]);
+        assert_eq!(prime_signature(&(2u64 * 3 * 5 * 7 * 11 * 13)), [1; 6]);
+        assert_eq!(prime_signature(&(1u64 << 40)), [40]);
+        assert_eq!(prime_signature(&1000000007u64), [1]);
+        assert_eq!(prime_signature(&(32u64 * 3 * 25 * 7)), [1, 1, 2, 5]);
 
-        // prime table boundary test
-        assert_eq!(prev_prime(&257u16, None), Some(251));
-        assert_eq!(next_prime(&251u16, None), Some(257));
-        assert_eq!(next_prime(&251u8, None), None);
-        assert_eq!(prev_prime(&8167u16, None), Some(8161));
-        assert_eq!(next_prime(&8161u16, None), Some(8167));
+        // numbers with the same signature have the same number of divisors
+        assert_eq!(prime_signature(&72u32), prime_signature(&200u32));
+        assert_eq!(divisor_sigma(&72u32, 0), divisor_sigma(&200u32, 0));
+    }
 
-        // OEIS:A077800
-        let twine_primes: [(u32, u32); 8] = [
-            (2, 3), // not exactly twine
-            (3, 5),
-            (5, 7),
-            (11, 13),
-            (17, 19),
-            (29, 31),
-            (41, 43),
-            (617, 619),
-        ];
-        for (p1, p2) in twine_primes {
-            assert_eq!(prev_prime(&p2, None).unwrap(), p1);
-            assert_eq!(next_prime(&p1, None).unwrap(), p2);
+    #[test]
+    fn is_powersmooth_test() {
+        assert!(is_powersmooth(&12u32, &4));
+        assert!(!is_powersmooth(&12u32, &3));
+        assert!(is_powersmooth(&1u32, &1));
+        assert!(!is_powersmooth(&0u32, &100));
+        assert!(is_powersmooth(&720720u64, &16));
+        assert!(!is_powersmooth(&720720u64, &15));
+        assert!(!is_powersmooth(&(1u64 << 40), &(1 << 39)));
+
+        for n in 2..1000u64 {
+            let factors = factorize64(n);
+            let max_pp = factors.into_iter().map(|(p, e)| p.pow(e as u32)).max();
+            let max_pp = max_pp.unwrap();
+            assert!(is_powersmooth(&n, &max_pp));
+            assert!(!is_powersmooth(&n, &(max_pp - 1)));
         }
+    }
 
-        let adj10_primes: [(u32, u32); 7] = [
-            (7, 11),
-            (97, 101),
-            (997, 1009),
-            (9973, 10007),
-            (99991, 100003),
-            (999983, 1000003),
-            (9999991, 10000019),
-        ];
-        for (i, (p1, p2)) in adj10_primes.iter().enumerate() {
-            assert_eq!(prev_prime(p2, None).unwrap(), *p1);
-            assert_eq!(next_prime(p1, None).unwrap(), *p2);
+    #[test]
+    fn gaussian_int_test() {
+        assert_eq!(GaussianInt::new(3, -4).norm(), 25);
+        assert_eq!(GaussianInt::new(i64::MIN, i64::MIN).norm(), 1 << 127);
 
-            let pow = 10u32.pow((i + 1) as u32);
-            assert_eq!(prev_prime(&pow, None).unwrap(), *p1);
-            assert_eq!(next_prime(&pow, None).unwrap(), *p2);
+        let primes = [(1, 1), (2, 1), (-1, 2), (3, 0), (0, -7), (4, 5), (-10, -1)];
+        for (re, im) in primes {
+            assert!(GaussianInt::new(re, im).is_prime_gaussian());
+        }
+        let composites = [(0, 0), (1, 0), (0, -1), (2, 0), (5, 0), (0, 13), (4, 3)];
+        for (re, im) in composites {
+            assert!(!GaussianInt::new(re, im).is_prime_gaussian());
+        }
+
+        // norm larger than 2^64
+        let g = GaussianInt::new(5000000000, 5000000011);
+        assert_eq!(g.is_prime_gaussian(), is_prime(&g.norm(), None).probably());
+    }
+
+    #[test]
+    fn jacobi_table_test() {
+        assert_eq!(jacobi_table(&1u8), vec![1]);
+        assert_eq!(jacobi_table(&7u8), vec![0, 1, 1, -1, 1, -1, -1]);
+
+        for n in [3u32, 9, 15, 21, 45, 101, 255, 1001, 8191, 10395] {
+            let table = jacobi_table(&n);
+            assert_eq!(table.len(), n as usize);
+            for (a, j) in table.into_iter().enumerate() {
+                assert_eq!(j, (a as u32).jacobi(&n), "({}|{})", a, n);
+            }
+        }
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let n = BigUint::from(1155u16);
+            for (a, j) in jacobi_table(&n).into_iter().enumerate() {
+                assert_eq!(j, BigUint::from(a).jacobi(&n));
+            }
         }
     }
 }
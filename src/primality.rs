@@ -407,6 +407,45 @@ mod tests {
         for p in [2, 5, 7, 13, 19] {
             assert!(561u32.is_prp(p));
         }
+
+        // Carmichael numbers (OEIS:A002997) pass the fermat test but fail the strong test
+        for c in [561u32, 1105, 1729, 2465, 2821, 6601, 8911] {
+            assert!(c.is_fermat_prp(2));
+            assert!(SmallMint::from(c).is_fermat_prp(2.into()));
+            assert!(!c.is_sprp(2));
+        }
+        assert!(!1105u32.is_fermat_prp(5));
+        assert!(1109u32.is_fermat_prp(2) && 1109u32.is_sprp(2));
+    }
+
+    #[test]
+    fn euler_prp_test() {
+        // Euler pseudoprimes of base 2 (OEIS:A047713) under 10000
+        let epsp: [u16; 12] = [
+            561, 1105, 1729, 1905, 2047, 2465, 3277, 4033, 4681, 6601, 8321, 8481,
+        ];
+        for psp in epsp {
+            assert!(psp.is_euler_prp(2));
+            assert!(SmallMint::from(psp).is_euler_prp(2.into()));
+        }
+
+        // 341 is a fermat pseudoprime but not an euler pseudoprime of base 2
+        assert!(341u16.is_prp(2) && !341u16.is_euler_prp(2));
+        assert!(!1u16.is_euler_prp(2));
+        assert!(2u16.is_euler_prp(3));
+
+        // the euler test is between the fermat test and the strong test
+        let mut count = 0;
+        for n in 3..10000u16 {
+            if n.is_euler_prp(2) {
+                assert!(n.is_prp(2));
+                count += 1;
+            }
+            if n.is_sprp(2) {
+                assert!(n.is_euler_prp(2));
+            }
+        }
+        assert_eq!(count, 1228 + epsp.len()); // π(10000) = 1229
     }
 
     #[test]
@@ -1,6 +1,6 @@
 use crate::mint::SmallMint;
 use crate::nt_funcs::{is_prime, is_prime64, next_prime};
-use crate::{PrimalityTestConfig, RandPrime};
+use crate::{PrimalityTestConfig, RandPrime, RandPrimeExt};
 #[cfg(feature = "num-bigint")]
 use num_bigint::{BigUint, RandBigInt};
 use rand::Rng;
@@ -76,6 +76,37 @@ macro_rules! impl_randprime_prim {
                 }
             }
         }
+
+        impl<R: Rng> RandPrimeExt<$T> for R {
+            fn try_gen_prime(
+                &mut self,
+                bit_size: usize,
+                max_attempts: usize,
+                _: Option<PrimalityTestConfig>,
+            ) -> Option<$T> {
+                if bit_size > (<$T>::BITS as usize) {
+                    panic!("The given bit size limit exceeded the capacity of the integer type!")
+                }
+                if bit_size == 0 {
+                    return None;
+                }
+
+                for _ in 0..max_attempts {
+                    let t: $T = self.gen();
+                    let t = (t >> (<$T>::BITS - bit_size as u32)) | 1; // filter even numbers
+                    if is_prime64(t as u64) {
+                        return Some(t);
+                    }
+                    // the next prime could exceed the bit size limit
+                    if let Some(p) = next_prime(&t, None) {
+                        if ((<$T>::BITS - p.leading_zeros()) as usize) <= bit_size {
+                            return Some(p);
+                        }
+                    }
+                }
+                None
+            }
+        }
     )*}
 }
 impl_randprime_prim!(u8 u16 u32 u64);
@@ -145,6 +176,37 @@ impl<R: Rng> RandPrime<u128> for R {
     }
 }
 
+impl<R: Rng> RandPrimeExt<u128> for R {
+    fn try_gen_prime(
+        &mut self,
+        bit_size: usize,
+        max_attempts: usize,
+        config: Option<PrimalityTestConfig>,
+    ) -> Option<u128> {
+        if bit_size > (u128::BITS as usize) {
+            panic!("The given bit size limit exceeded the capacity of the integer type!")
+        }
+        if bit_size == 0 {
+            return None;
+        }
+
+        for _ in 0..max_attempts {
+            let t: u128 = self.gen();
+            let t = (t >> (u128::BITS - bit_size as u32)) | 1; // filter even numbers
+            if is_prime(&SmallMint::from(t), config).probably() {
+                return Some(t);
+            }
+            // the next prime could exceed the bit size limit
+            if let Some(p) = next_prime(&t, config) {
+                if ((u128::BITS - p.leading_zeros()) as usize) <= bit_size {
+                    return Some(p);
+                }
+            }
+        }
+        None
+    }
+}
+
 #[cfg(feature = "num-bigint")]
 impl<R: Rng> RandPrime<BigUint> for R {
     #[inline]
@@ -201,6 +263,35 @@ impl<R: Rng> RandPrime<BigUint> for R {
     }
 }
 
+#[cfg(feature = "num-bigint")]
+impl<R: Rng> RandPrimeExt<BigUint> for R {
+    fn try_gen_prime(
+        &mut self,
+        bit_size: usize,
+        max_attempts: usize,
+        config: Option<PrimalityTestConfig>,
+    ) -> Option<BigUint> {
+        if bit_size == 0 {
+            return None;
+        }
+
+        for _ in 0..max_attempts {
+            let mut t = self.gen_biguint(bit_size as u64);
+            t.set_bit(0, true); // filter even numbers
+            if is_prime(&t, config).probably() {
+                return Some(t);
+            }
+            // the next prime could exceed the bit size limit
+            if let Some(p) = next_prime(&t, config) {
+                if p.bits() as usize <= bit_size {
+                    return Some(p);
+                }
+            }
+        }
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +336,38 @@ mod tests {
         assert!(p < (1 << 24));
     }
 
+    #[test]
+    fn try_rand_prime() {
+        let mut rng = rand::thread_rng();
+
+        let p: u16 = rng.try_gen_prime(12, 100, None).unwrap();
+        assert!(is_prime64(p as u64) && p < (1 << 12));
+        let p: u64 = rng.try_gen_prime(64, 100, None).unwrap();
+        assert!(is_prime64(p));
+        let p: u128 = rng.try_gen_prime(100, 100, None).unwrap();
+        assert!(is_prime(&p, None).probably() && p < (1 << 100));
+
+        // there's no prime with one bit, and no attempt means no prime
+        let p: Option<u8> = rng.try_gen_prime(1, 1000, None);
+        assert_eq!(p, None);
+        let p: Option<u128> = rng.try_gen_prime(1, 1000, None);
+        assert_eq!(p, None);
+        let p: Option<u8> = rng.try_gen_prime(0, 1000, None);
+        assert_eq!(p, None);
+        let p: Option<u64> = rng.try_gen_prime(64, 0, None);
+        assert_eq!(p, None);
+        let p: u8 = rng.try_gen_prime(2, 1000, None).unwrap();
+        assert!(p == 2 || p == 3);
+
+        #[cfg(feature = "num-bigint")]
+        {
+            let p: BigUint = rng.try_gen_prime(256, 100, None).unwrap();
+            assert!(is_prime(&p, None).probably() && p.bits() <= 256);
+            let p: Option<BigUint> = rng.try_gen_prime(1, 100, None);
+            assert_eq!(p, None);
+        }
+    }
+
     #[test]
     fn rand_prime_exact() {
         let mut rng = rand::thread_rng();
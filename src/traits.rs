@@ -1,9 +1,10 @@
 use core::default::Default;
-use std::ops::{BitAnd, BitOr};
+use std::ops::{BitAnd, BitOr, Shr};
 
 use either::Either;
 use num_integer::{Integer, Roots};
-use num_traits::Pow;
+use num_modular::{ModularRefOps, ModularUnaryOps};
+use num_traits::{FromPrimitive, NumRef, One, Pow, RefNum, Zero};
 
 /// This trait support unified bit testing for (unsigned) integers
 pub trait BitTest {
@@ -76,6 +77,82 @@ impl BitOr<Primality> for Primality {
     }
 }
 
+/// This enum describes the estimated difficulty of factorizing an integer,
+/// see [factorization_hint][crate::nt_funcs::factorization_hint]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactorDifficulty {
+    /// The number is (probably) a prime, no factorization is needed.
+    Prime,
+    /// The number can be factorized quickly, because it's small or
+    /// it's fully factored by trial division with small primes.
+    Easy,
+    /// The number has a large composite cofactor without small prime factors (such as
+    /// an RSA-like semiprime), full factorization can be expensive.
+    Hard,
+}
+
+/// This enum describes how a prime factor is found during factorization,
+/// see [factors_annotated][crate::nt_funcs::factors_annotated]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FactorMethod {
+    /// The target itself is a (probable) prime, no factorization is done.
+    Prime,
+    /// The factor is found by trial division with small primes, or it's the prime cofactor
+    /// left after the trial division.
+    TrialDivision,
+    /// The factor is the root of a perfect power.
+    PerfectPower,
+    /// The factor is split off by Pollard's rho method.
+    PollardRho,
+    /// The factor is split off by Hart's one line method.
+    OneLine,
+    /// The factor is split off by Shanks's square forms factorization (SQUFOF).
+    Squfof,
+}
+
+/// A binary tree recording how an integer is split during factorization,
+/// see [factor_tree][crate::nt_funcs::factor_tree]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FactorTree<T> {
+    /// A (probable) prime factor
+    Prime(T),
+    /// A composite that is split into a divisor and the cofactor
+    Composite {
+        value: T,
+        divisor: Box<FactorTree<T>>,
+        cofactor: Box<FactorTree<T>>,
+    },
+    /// An integer that is not split further. It's a composite on which the factorization
+    /// failed, or zero or one.
+    Unfactored(T),
+}
+
+impl<T> FactorTree<T> {
+    /// The integer represented by this node
+    pub fn value(&self) -> &T {
+        match self {
+            FactorTree::Prime(v) | FactorTree::Unfactored(v) => v,
+            FactorTree::Composite { value, .. } => value,
+        }
+    }
+
+    /// Get all leaves of the tree from left to right. The product of the leaves equals to
+    /// the value of the root.
+    pub fn leaves(&self) -> Vec<&FactorTree<T>> {
+        match self {
+            FactorTree::Composite {
+                divisor, cofactor, ..
+            } => {
+                let mut leaves = divisor.leaves();
+                leaves.extend(cofactor.leaves());
+                leaves
+            }
+            leaf => vec![leaf],
+        }
+    }
+}
+
 /// Represents a configuration for a primality test
 #[derive(Debug, Clone, Copy)]
 #[non_exhaustive]
@@ -88,11 +165,22 @@ pub struct PrimalityTestConfig {
     /// Number of strong probable prime test with random bases
     pub sprp_random_trials: usize,
 
+    /// Whether perform strong probable prime tests with bases 2, 3 and 5 before any other tests,
+    /// which rejects most composites quickly. It doesn't affect the reported probability.
+    pub fast_reject: bool,
+
+    /// Whether perform Euler (Solovay-Strassen) probable prime test with base 2
+    pub euler_test: bool,
+
     /// Whether perform strong lucas probable prime test (with automatically selected parameters)
     pub slprp_test: bool,
 
     /// Whether perform extra strong lucas probable prime test (with automatically selected parameters)
     pub eslprp_test: bool,
+
+    /// If set, the primality is decided by trial division only, with odd divisors up to
+    /// the given bound. All the probable prime tests above are skipped in this case.
+    pub trial_bound: Option<u64>,
 }
 
 impl Default for PrimalityTestConfig {
@@ -102,8 +190,11 @@ impl Default for PrimalityTestConfig {
         Self {
             sprp_trials: 2,        // test base 2 and 3
             sprp_random_trials: 3, // choose other 3 random bases
+            fast_reject: false,
+            euler_test: false,
             slprp_test: false,
             eslprp_test: false,
+            trial_bound: None,
         }
     }
 }
@@ -123,11 +214,62 @@ impl PrimalityTestConfig {
         Self {
             sprp_trials: 1,
             sprp_random_trials: 0,
+            fast_reject: false,
+            euler_test: false,
             slprp_test: true,
             eslprp_test: false,
+            trial_bound: None,
+        }
+    }
+
+    /// Create a configuration that tests primality purely by trial division with divisors
+    /// up to `bound`, without any probable prime test.
+    ///
+    /// The result is deterministic ([Primality::Yes] or [Primality::No]) if the target is
+    /// below `bound^2`. Otherwise a target without a divisor under the bound is reported as
+    /// [Primality::Probable] with probability zero, since no primality evidence is collected.
+    pub fn trial_only(bound: u64) -> Self {
+        Self {
+            sprp_trials: 0,
+            sprp_random_trials: 0,
+            fast_reject: false,
+            euler_test: false,
+            slprp_test: false,
+            eslprp_test: false,
+            trial_bound: Some(bound),
         }
     }
 
+    /// Create a configuration tuned for targets with the given bit size. This is the
+    /// configuration used by [is_prime][crate::nt_funcs::is_prime] when no config is specified.
+    ///
+    /// - Up to 64 bits, SPRP tests with the first 12 prime bases are used, which are deterministic
+    ///   below 3.18 * 10^23 (although u64 targets are tested by [is_prime64][crate::nt_funcs::is_prime64] anyway).
+    /// - Up to 128 bits, the Baillie-PSW test is used.
+    /// - For larger (cryptographic) sizes, the Baillie-PSW test is followed by a few SPRP tests
+    ///   with random bases, the number of rounds roughly follows FIPS 186-4 Appendix C.3.
+    pub fn recommended_for(bit_size: usize) -> Self {
+        let mut config = Self::bpsw();
+        match bit_size {
+            0..=64 => {
+                config.sprp_trials = 12;
+                config.slprp_test = false;
+            }
+            65..=128 => {}
+            129..=512 => config.sprp_random_trials = 5,
+            513..=1024 => config.sprp_random_trials = 4,
+            _ => config.sprp_random_trials = 3,
+        };
+        config
+    }
+
+    /// Enable or disable the pre-check with small bases, see the `fast_reject` field.
+    /// This is useful when testing many random candidates, such as during prime generation.
+    pub fn fast_reject(mut self, enabled: bool) -> Self {
+        self.fast_reject = enabled;
+        self
+    }
+
     /// Create a configuration for PSW test (base 2 SPRP + Fibonacci test)
     fn psw() {
         todo!() // TODO: implement Fibonacci PRP
@@ -141,13 +283,18 @@ pub struct FactorizationConfig {
     /// Config for testing if a factor is prime
     pub primality_config: PrimalityTestConfig,
 
-    /// Prime limit of trial division, you also need to reserve the primes in the buffer
-    /// if all primes under the limit are to be tested. `None` means using all available primes.
+    /// Prime limit of trial division. Primes beyond the buffer are covered by the candidates
+    /// of a small wheel, so the buffer doesn't need to be extended to the limit.
+    /// `None` means using all the primes available in the buffer.
     pub td_limit: Option<u64>,
 
     /// Number of trials with Pollard's rho method
     pub rho_trials: usize,
 
+    /// Use the binary GCD algorithm ([binary_gcd][crate::nt_funcs::binary_gcd]) instead of the
+    /// Euclidean algorithm in the gcd steps of Pollard's rho method
+    pub binary_gcd: bool,
+
     /// Number of trials with Pollard's p-1 method
     pm1_trials: usize,
 
@@ -164,6 +311,7 @@ impl Default for FactorizationConfig {
             primality_config: PrimalityTestConfig::default(),
             td_limit: Some(THRESHOLD_DEFAULT_TD),
             rho_trials: 4,
+            binary_gcd: false,
             pm1_trials: 0,
             pp1_trials: 0,
         }
@@ -213,6 +361,24 @@ pub trait ExactRoots: Roots + Pow<u32, Output = Self> + Clone {
 //      FLINT `n_is_perfect_power235`, `fmpz_is_perfect_power`
 //      GMP `mpz_perfect_power_p`
 
+/// Minimal set of arithmetic primitives needed by the generic modular algorithms
+/// ([generic_powm][crate::nt_funcs::generic_powm], [generic_invm][crate::nt_funcs::generic_invm]
+/// and [generic_jacobi][crate::nt_funcs::generic_jacobi]).
+///
+/// These are standalone functions for external integer types (e.g. fixed 256/512-bit integers),
+/// which can implement this adapter to get the algorithms for free. The integer types supported
+/// by this crate don't go through this trait, they use the modular arithmetic of `num-modular`.
+pub trait ModularPrimitives: Clone + PartialEq + Zero + One {
+    /// Calculate `(self * rhs) mod m`, where both operands are already reduced modulo m
+    fn mulm(&self, rhs: &Self, m: &Self) -> Self;
+
+    /// Calculate `(self - rhs) mod m`, where both operands are already reduced modulo m
+    fn subm(&self, rhs: &Self, m: &Self) -> Self;
+
+    /// Calculate the quotient and the remainder of `self / rhs`
+    fn div_rem(&self, rhs: &Self) -> (Self, Self);
+}
+
 /// This trait represents a general data structure that stores primes.
 ///
 /// It's recommended to store at least a bunch of small primes in the buffer
@@ -245,9 +411,52 @@ pub trait PrimalityUtils: Integer + Clone {
     /// Test if the integer is a (Fermat) probable prime
     fn is_prp(&self, base: Self) -> bool;
 
+    /// Test if the integer is a Fermat probable prime, i.e. `base^(n-1) = 1 (mod n)`.
+    ///
+    /// This is the same test as [is_prp][Self::is_prp]. It's weaker than [is_sprp][Self::is_sprp]
+    /// (e.g. Carmichael numbers pass the test for all coprime bases), but slightly cheaper.
+    #[inline]
+    fn is_fermat_prp(&self, base: Self) -> bool {
+        self.is_prp(base)
+    }
+
     /// Test if the integer is a strong probable prime (based on Miller-Rabin test).
     fn is_sprp(&self, base: Self) -> bool;
 
+    /// Test if the integer is an Euler probable prime (based on Solovay-Strassen test), i.e.
+    /// `base^((n-1)/2) = (base/n) (mod n)` where `(base/n)` is the Jacobi symbol.
+    ///
+    /// Its strength is between the Fermat test ([is_prp][Self::is_prp]) and the strong test
+    /// ([is_sprp][Self::is_sprp]). The default implementation is available for the integer
+    /// types supporting modular arithmetics.
+    fn is_euler_prp(&self, base: Self) -> bool
+    where
+        Self: FromPrimitive + NumRef + ModularRefOps,
+        for<'r> &'r Self:
+            RefNum<Self> + Shr<usize, Output = Self> + ModularUnaryOps<&'r Self, Output = Self>,
+    {
+        if self <= &Self::one() {
+            return false;
+        }
+        if self.is_even() {
+            return self == &Self::from_u8(2).unwrap();
+        }
+
+        // prevent reduction if the input is in montgomery form
+        let m1 = Self::one() % self;
+        let mm1 = (&m1).negm(self);
+
+        let tm1 = self - Self::one();
+        let e = &tm1 >> 1;
+        let x = base.jacobi(self);
+        let y = base.powm(&e, self);
+        match x {
+            1 => y == m1,
+            -1 => y == mm1,
+            _ => false,
+        }
+    }
+
     /// Do a Miller-Rabin test. The return value is a integer if it finds a factor of
     /// the integer, otherwise it reports the test result.
     fn test_sprp(&self, base: Self) -> Either<bool, Self>;
@@ -299,3 +508,19 @@ pub trait RandPrime<T> {
     /// if the bit_size is 0 or it's larger than the bit width of the integer
     fn gen_safe_prime_exact(&mut self, bit_size: usize) -> T;
 }
+
+/// Extension of [RandPrime] with fallible generation of primes
+pub trait RandPrimeExt<T>: RandPrime<T> {
+    /// Generate a random prime within the given bit size limit, giving up after `max_attempts`
+    /// random candidates. Unlike [gen_prime][RandPrime::gen_prime], it never loops indefinitely,
+    /// and it returns [None] if no prime is found (e.g. there's no prime with bit_size 1).
+    ///
+    /// # Panics
+    /// if the bit_size is larger than the bit width of the integer
+    fn try_gen_prime(
+        &mut self,
+        bit_size: usize,
+        max_attempts: usize,
+        config: Option<PrimalityTestConfig>,
+    ) -> Option<T>;
+}